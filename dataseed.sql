@@ -759,14 +759,14 @@ VALUES
         1667731667, 1, 1, 34, strftime('%s', 'now')
     );
 
-INSERT INTO `places` (`uuid`, `name`, `street`, `city`, `country_code`, `type`, `created_at`)
+INSERT INTO `places` (`uuid`, `name`, `street`, `city`, `country_code`, `type`, `latitude`, `longitude`, `created_at`)
 VALUES
-    ('7f55e43e-dd84-4ef3-8d3d-ed5b312e3ef7', 'Smoczy Czajnik', 'Francuska 15', 'Katowice', 'PL', 'public', strftime('%s', 'now')),
-    ('1c6ef423-6a91-47e3-b4df-7734558ea232', 'Cajovna u cajoveho skřítka', 'Heyrovského 1725', 'Ostrava', 'CZ', 'public', strftime('%s', 'now')),
-    ('fa64cb47-67c5-4553-98c5-ed16c6b5c682', 'Boardowa', 'Topolowa 52', 'Kraków', 'PL', 'public', strftime('%s', 'now')),
-    ('8a0fd189-b4a2-4f2f-be84-ff66613d14cc', 'Synercom Oddział Mysłowice', 'Piastów Śląskich 18a', 'Mysłowice', 'PL', 'public', strftime('%s', 'now')),
-    ('75e0d15f-8547-4b5b-adb7-1fdbdf8f1667', 'Drzwi zwane koniem', 'Warszawska 37', 'Katowice', 'PL', 'public', strftime('%s', 'now')),
-    ('314169a4-19da-427b-9d29-7a1c9499ad83', 'Herbaciarnia Satomi', 'Ogrodnicza 1', 'Pisarzowice', 'PL', 'public', strftime('%s', 'now'));
+    ('7f55e43e-dd84-4ef3-8d3d-ed5b312e3ef7', 'Smoczy Czajnik', 'Francuska 15', 'Katowice', 'PL', 'club', 50.2546, 19.0297, strftime('%s', 'now')),
+    ('1c6ef423-6a91-47e3-b4df-7734558ea232', 'Cajovna u cajoveho skřítka', 'Heyrovského 1725', 'Ostrava', 'CZ', 'cafe', 49.8322, 18.1648, strftime('%s', 'now')),
+    ('fa64cb47-67c5-4553-98c5-ed16c6b5c682', 'Boardowa', 'Topolowa 52', 'Kraków', 'PL', 'club', 50.0878, 19.9610, strftime('%s', 'now')),
+    ('8a0fd189-b4a2-4f2f-be84-ff66613d14cc', 'Synercom Oddział Mysłowice', 'Piastów Śląskich 18a', 'Mysłowice', 'PL', 'tournament_hall', 50.2413, 19.1410, strftime('%s', 'now')),
+    ('75e0d15f-8547-4b5b-adb7-1fdbdf8f1667', 'Drzwi zwane koniem', 'Warszawska 37', 'Katowice', 'PL', 'club', 50.2590, 19.0335, strftime('%s', 'now')),
+    ('314169a4-19da-427b-9d29-7a1c9499ad83', 'Herbaciarnia Satomi', 'Ogrodnicza 1', 'Pisarzowice', 'PL', 'cafe', 49.8590, 19.1150, strftime('%s', 'now'));
 
 INSERT INTO `ranks_cache` (uuid, ranking_uuid, name, required_points, required_exam, color, created_at)
 VALUES
@@ -0,0 +1,23 @@
+--
+-- rankings, ranks and players are synchronized from federation export (see src/sync.rs),
+-- records missing in the export are soft-deleted by setting deleted_at
+--
+
+ALTER TABLE `rankings_cache` ADD COLUMN `deleted_at` INTEGER NULL;
+ALTER TABLE `ranks_cache` ADD COLUMN `deleted_at` INTEGER NULL;
+ALTER TABLE `players_cache` ADD COLUMN `deleted_at` INTEGER NULL;
+
+-- report of every sync run, counts is json object of inserted / updated / deleted / unchanged
+-- records per table, error is set when sync failed and nothing was applied,
+-- rejected_players_uuids is json array of players rejected by the run
+-- so admins can fix them in the federation export
+CREATE TABLE `sync_reports` (
+    `uuid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `source` TEXT NOT NULL,
+    `is_dry_run` INTEGER NOT NULL,
+    `counts` TEXT NULL,
+    `rejected_players_uuids` TEXT NULL,
+    `error` TEXT NULL,
+    `started_at` INTEGER NOT NULL,
+    `finished_at` INTEGER NOT NULL
+);
@@ -0,0 +1,16 @@
+--
+-- achievements awarded to players, refreshed on every ranking computation.
+-- achievement_key refers to catalogue in src/achievements.rs, game_session_uuid is the game
+-- after which the achievement was awarded and awarded_at is the time that game ended
+--
+
+CREATE TABLE `player_achievements_cache` (
+    `ranking_uuid` TEXT NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `achievement_key` TEXT NOT NULL,
+    `game_session_uuid` TEXT NOT NULL COLLATE BINARY,
+    `awarded_at` INTEGER NOT NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX `player_achievements_cache_uidx` ON `player_achievements_cache` (`ranking_uuid`, `player_uuid`, `achievement_key`);
@@ -0,0 +1,8 @@
+--
+-- region_code is ISO 3166-2 code derived from free-text region, NULL when region is not known.
+-- country and region codes are normalized on application start (see src/geo.rs)
+--
+
+ALTER TABLE `players_cache` ADD COLUMN `region_code` TEXT NULL;
+
+CREATE INDEX `players_cache_region_idx` ON `players_cache` (`ranking_uuid`, `country_code`, `region_code`);
@@ -0,0 +1,23 @@
+--
+-- places submitted by users are pending until approved or rejected by an admin,
+-- only approved and not archived places are listed
+--
+
+ALTER TABLE `places` ADD COLUMN `status` TEXT NOT NULL DEFAULT 'approved';
+ALTER TABLE `places` ADD COLUMN `creator_uid` TEXT NULL COLLATE BINARY;
+ALTER TABLE `places` ADD COLUMN `moderator_uid` TEXT NULL COLLATE BINARY;
+ALTER TABLE `places` ADD COLUMN `moderated_at` INTEGER NULL;
+ALTER TABLE `places` ADD COLUMN `rejection_reason` TEXT NULL;
+ALTER TABLE `places` ADD COLUMN `updated_at` INTEGER NULL;
+ALTER TABLE `places` ADD COLUMN `archived_at` INTEGER NULL;
+
+-- type is one of club, cafe, private_home, tournament_hall, places created before types
+-- are 'unknown' and stay in the moderation queue until an admin picks their type
+UPDATE `places` SET `type` = 'unknown' WHERE `type` NOT IN ('club', 'cafe', 'private_home', 'tournament_hall');
+
+CREATE INDEX `places_status_idx` ON `places` (`status`, `created_at`);
+
+-- names of rejected and archived places are free to be used by other places,
+-- so the name uniqueness is limited to pending and approved places in use
+DROP INDEX `places_name_uidx`;
+CREATE UNIQUE INDEX `places_name_uidx` ON `places` (`name` ASC) WHERE `status` != 'rejected' AND `archived_at` IS NULL;
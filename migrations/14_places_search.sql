@@ -0,0 +1,17 @@
+--
+-- full-text index of places, name, street and city are stored folded (see src/search.rs)
+-- so `lodz` matches `Łódź`, rows are written by the application on place create and update
+--
+
+CREATE VIRTUAL TABLE `places_search` USING fts5(
+    `place_uuid` UNINDEXED,
+    `name`,
+    `street`,
+    `city`,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER `places_search_delete` AFTER DELETE ON `places`
+BEGIN
+    DELETE FROM `places_search` WHERE `place_uuid` = old.`uuid`;
+END;
@@ -0,0 +1,8 @@
+--
+-- optional location of the place in WGS 84 degrees, used to suggest nearby places
+--
+
+ALTER TABLE `places` ADD COLUMN `latitude` REAL NULL;
+ALTER TABLE `places` ADD COLUMN `longitude` REAL NULL;
+
+CREATE INDEX `places_location_idx` ON `places` (`latitude`, `longitude`);
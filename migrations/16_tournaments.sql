@@ -0,0 +1,47 @@
+--
+-- tournaments within a ranking, game sessions are linked to tournament rounds
+-- and standings are computed from their final scores (see src/tournaments.rs)
+--
+
+-- status is one of registration, in_progress, finished.
+-- uma is json array of four points added by placement, tie_breakers is json array
+-- of tie-breakers applied in order when tournament points are equal
+CREATE TABLE `tournaments` (
+    `uuid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `ranking_uuid` TEXT NOT NULL COLLATE BINARY,
+    `place_uuid` TEXT NULL COLLATE BINARY,
+    `name` TEXT NOT NULL,
+    `status` TEXT NOT NULL,
+    `rounds_count` INTEGER NOT NULL,
+    `return_points` INTEGER NOT NULL,
+    `uma` TEXT NOT NULL,
+    `tie_breakers` TEXT NOT NULL,
+    `creator_uid` TEXT NOT NULL COLLATE BINARY,
+    `starts_at` INTEGER NULL,
+    `finished_at` INTEGER NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE INDEX `tournaments_ranking_uuid_idx` ON `tournaments` (`ranking_uuid`, `created_at`);
+
+CREATE TABLE `tournament_players` (
+    `tournament_uuid` TEXT NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `checked_in_at` INTEGER NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX `tournament_players_uidx` ON `tournament_players` (`tournament_uuid`, `player_uuid`);
+
+CREATE TABLE `tournament_rounds` (
+    `uuid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `tournament_uuid` TEXT NOT NULL COLLATE BINARY,
+    `number` INTEGER NOT NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX `tournament_rounds_uidx` ON `tournament_rounds` (`tournament_uuid`, `number`);
+
+ALTER TABLE `game_sessions` ADD COLUMN `tournament_round_uuid` TEXT NULL COLLATE BINARY;
+
+CREATE INDEX `game_sessions_tournament_uuid_idx` ON `game_sessions` (`tournament_uuid`);
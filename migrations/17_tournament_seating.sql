@@ -0,0 +1,24 @@
+--
+-- generated seating of tournament rounds (see src/seating.rs)
+--
+
+-- checked-in players sitting the round out when they don't fill a table
+CREATE TABLE `tournament_byes` (
+    `tournament_uuid` TEXT NOT NULL COLLATE BINARY,
+    `tournament_round_uuid` TEXT NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX `tournament_byes_uidx` ON `tournament_byes` (`tournament_round_uuid`, `player_uuid`);
+CREATE INDEX `tournament_byes_tournament_uuid_idx` ON `tournament_byes` (`tournament_uuid`);
+
+-- guest players filling incomplete tables, reused by later rounds of the tournament
+-- and left out of standings
+CREATE TABLE `tournament_substitutes` (
+    `tournament_uuid` TEXT NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX `tournament_substitutes_uidx` ON `tournament_substitutes` (`tournament_uuid`, `player_uuid`);
@@ -0,0 +1,69 @@
+--
+-- leagues within a ranking played on scheduled match days, only game sessions flagged
+-- is_league_game and linked to a league fixture count toward league tables (see src/leagues.rs)
+--
+
+-- kind is one of individual, team. uma is json array of four points added by placement
+CREATE TABLE `leagues` (
+    `uuid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `ranking_uuid` TEXT NOT NULL COLLATE BINARY,
+    `name` TEXT NOT NULL,
+    `kind` TEXT NOT NULL,
+    `return_points` INTEGER NOT NULL,
+    `uma` TEXT NOT NULL,
+    `creator_uid` TEXT NOT NULL COLLATE BINARY,
+    `starts_at` INTEGER NULL,
+    `ends_at` INTEGER NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE INDEX `leagues_ranking_uuid_idx` ON `leagues` (`ranking_uuid`, `created_at`);
+
+-- single player of individual league or team with fixed roster, teams are named
+CREATE TABLE `league_entrants` (
+    `uuid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `league_uuid` TEXT NOT NULL COLLATE BINARY,
+    `name` TEXT NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE INDEX `league_entrants_league_uuid_idx` ON `league_entrants` (`league_uuid`);
+
+CREATE TABLE `league_entrant_players` (
+    `league_uuid` TEXT NOT NULL COLLATE BINARY,
+    `entrant_uuid` TEXT NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX `league_entrant_players_uidx` ON `league_entrant_players` (`league_uuid`, `player_uuid`);
+
+CREATE TABLE `league_match_days` (
+    `uuid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `league_uuid` TEXT NOT NULL COLLATE BINARY,
+    `number` INTEGER NOT NULL,
+    `place_uuid` TEXT NOT NULL COLLATE BINARY,
+    `scheduled_at` INTEGER NOT NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX `league_match_days_uidx` ON `league_match_days` (`league_uuid`, `number`);
+
+-- four entrants meeting at a table of the match day, any number of game sessions
+-- can be played within a fixture, each seating one player of every entrant
+CREATE TABLE `league_fixtures` (
+    `uuid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `league_uuid` TEXT NOT NULL COLLATE BINARY,
+    `match_day_uuid` TEXT NOT NULL COLLATE BINARY,
+    `entrant1_uuid` TEXT NOT NULL COLLATE BINARY,
+    `entrant2_uuid` TEXT NOT NULL COLLATE BINARY,
+    `entrant3_uuid` TEXT NOT NULL COLLATE BINARY,
+    `entrant4_uuid` TEXT NOT NULL COLLATE BINARY,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE INDEX `league_fixtures_league_uuid_idx` ON `league_fixtures` (`league_uuid`, `match_day_uuid`);
+
+ALTER TABLE `game_sessions` ADD COLUMN `league_fixture_uuid` TEXT NULL COLLATE BINARY;
+
+CREATE INDEX `game_sessions_league_fixture_uuid_idx` ON `game_sessions` (`league_fixture_uuid`);
@@ -0,0 +1,6 @@
+--
+-- EMA ids of players imported from federation export, required by EMA results export
+-- of tournaments (see src/tournaments.rs)
+--
+
+ALTER TABLE `players_cache` ADD COLUMN `ema_id` TEXT NULL;
@@ -0,0 +1,33 @@
+--
+-- rankings are computed by replaying game session events,
+-- every ranking picks rating algorithm used to compute elo_points (elo, glicko2, openskill)
+--
+
+ALTER TABLE `rankings_cache` ADD COLUMN `rating_algorithm` TEXT NOT NULL DEFAULT 'elo';
+ALTER TABLE `rankings_cache` ADD COLUMN `computed_at` INTEGER NULL;
+
+-- deviation and volatility are NULL when rating algorithm does not provide them
+ALTER TABLE `ranking_snapshot_cache` ADD COLUMN `rating_deviation` REAL NULL;
+ALTER TABLE `ranking_snapshot_cache` ADD COLUMN `rating_volatility` REAL NULL;
+
+CREATE INDEX `game_session_events_game_session_uuid_idx` ON `game_session_events` (`game_session_uuid`);
+
+-- table which stores cached, computed result of every player of game session
+-- seat is int-indexed in order of player1_uuid..player4_uuid => 0..3
+CREATE TABLE `game_session_results_cache` (
+    `game_session_uuid` TEXT NOT NULL COLLATE BINARY,
+    `ranking_uuid` TEXT NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `seat` INTEGER NOT NULL,
+    `placement` INTEGER NOT NULL,
+    `points` INTEGER NOT NULL,
+    `rating_before` REAL NOT NULL,
+    `rating_after` REAL NOT NULL,
+    `rating_deviation_after` REAL NULL,
+    `rank_points` INTEGER NOT NULL,
+    `ended_at` INTEGER NOT NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE INDEX `game_session_results_cache_player_idx` ON `game_session_results_cache` (`ranking_uuid`, `player_uuid`);
+CREATE INDEX `game_session_results_cache_game_session_idx` ON `game_session_results_cache` (`game_session_uuid`);
@@ -0,0 +1,27 @@
+--
+-- teams of four players competing within a tournament or the whole ranking, game results
+-- of members count toward the team while they are members (see src/teams.rs)
+--
+
+-- teams without tournament_uuid compete in the ranking
+CREATE TABLE `teams` (
+    `uuid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `ranking_uuid` TEXT NOT NULL COLLATE BINARY,
+    `tournament_uuid` TEXT NULL COLLATE BINARY,
+    `name` TEXT NOT NULL,
+    `creator_uid` TEXT NOT NULL COLLATE BINARY,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE INDEX `teams_ranking_uuid_idx` ON `teams` (`ranking_uuid`, `tournament_uuid`);
+
+-- membership is current while left_at is NULL, past memberships are kept
+CREATE TABLE `team_members` (
+    `team_uuid` TEXT NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `joined_at` INTEGER NOT NULL,
+    `left_at` INTEGER NULL
+);
+
+CREATE INDEX `team_members_team_uuid_idx` ON `team_members` (`team_uuid`, `player_uuid`);
+CREATE INDEX `team_members_player_uuid_idx` ON `team_members` (`player_uuid`);
@@ -0,0 +1,20 @@
+--
+-- per-ranking rules deciding who is listed on the public leaderboard
+--  * min_games, min_opponents - required number of computed games and distinct opponents
+--  * inactivity_days - days without game after which inactivity_policy applies, NULL disables it
+--  * inactivity_policy - 'hide' unlists player, 'decay' lowers elo_points by inactivity_decay per day
+--    (never below initial rating of the rating algorithm)
+--
+
+ALTER TABLE `rankings_cache` ADD COLUMN `min_games` INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE `rankings_cache` ADD COLUMN `min_opponents` INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE `rankings_cache` ADD COLUMN `inactivity_days` INTEGER NULL;
+ALTER TABLE `rankings_cache` ADD COLUMN `inactivity_policy` TEXT NOT NULL DEFAULT 'hide';
+ALTER TABLE `rankings_cache` ADD COLUMN `inactivity_decay` REAL NOT NULL DEFAULT 0;
+
+-- unlisted_reason is a human readable explanation, i.e. "3 more games needed"
+ALTER TABLE `ranking_snapshot_cache` ADD COLUMN `games_count` INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE `ranking_snapshot_cache` ADD COLUMN `opponents_count` INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE `ranking_snapshot_cache` ADD COLUMN `last_game_at` INTEGER NULL;
+ALTER TABLE `ranking_snapshot_cache` ADD COLUMN `is_listed` INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE `ranking_snapshot_cache` ADD COLUMN `unlisted_reason` TEXT NULL;
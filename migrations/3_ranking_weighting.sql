@@ -0,0 +1,14 @@
+--
+-- weighting of rating changes of a game session
+--  * unranked sessions only count for statistics, weight is 0
+--  * in novice friendly sessions rating change of experienced players is multiplied by novice_friendly_weight,
+--    rating loss of newcomers (less than novice_games computed games) is multiplied by novice_loss_weight
+--
+
+ALTER TABLE `rankings_cache` ADD COLUMN `novice_games` INTEGER NOT NULL DEFAULT 10;
+ALTER TABLE `rankings_cache` ADD COLUMN `novice_friendly_weight` REAL NOT NULL DEFAULT 0.5;
+ALTER TABLE `rankings_cache` ADD COLUMN `novice_loss_weight` REAL NOT NULL DEFAULT 0;
+
+-- weighting is one of 'standard', 'unranked', 'novice_friendly', 'novice_protected'
+ALTER TABLE `game_session_results_cache` ADD COLUMN `weight` REAL NOT NULL DEFAULT 1;
+ALTER TABLE `game_session_results_cache` ADD COLUMN `weighting` TEXT NOT NULL DEFAULT 'standard';
@@ -0,0 +1,31 @@
+--
+-- table which stores cached statistics of player within ranking,
+-- refreshed on every ranking computation. contains unranked games as well
+--  * hands_count doesn't contain hands ended with chonbo
+--  * average_hand_value is average of points paid to player for won hands excluding riichi sticks
+--
+
+CREATE TABLE `player_stats_cache` (
+    `ranking_uuid` TEXT NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `games_count` INTEGER NOT NULL,
+    `placement1_count` INTEGER NOT NULL,
+    `placement2_count` INTEGER NOT NULL,
+    `placement3_count` INTEGER NOT NULL,
+    `placement4_count` INTEGER NOT NULL,
+    `hands_count` INTEGER NOT NULL,
+    `wins_count` INTEGER NOT NULL,
+    `tsumo_count` INTEGER NOT NULL,
+    `ron_count` INTEGER NOT NULL,
+    `deal_in_count` INTEGER NOT NULL,
+    `riichi_count` INTEGER NOT NULL,
+    `chonbo_count` INTEGER NOT NULL,
+    `average_hand_value` REAL NULL,
+    `best_game_session_uuid` TEXT NULL COLLATE BINARY,
+    `best_game_points` INTEGER NULL,
+    `worst_game_session_uuid` TEXT NULL COLLATE BINARY,
+    `worst_game_points` INTEGER NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX `player_stats_cache_player_uidx` ON `player_stats_cache` (`ranking_uuid`, `player_uuid`);
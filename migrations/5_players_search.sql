@@ -0,0 +1,8 @@
+--
+-- search_terms contains folded (lowercase, without diacritics) words of nickname, first_name,
+-- last_name and usma_id, each prefixed with space. empty terms are filled on application start
+--
+
+ALTER TABLE `players_cache` ADD COLUMN `search_terms` TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX `players_cache_ranking_uuid_idx` ON `players_cache` (`ranking_uuid`);
@@ -0,0 +1,9 @@
+--
+-- erased_at is set when player requested erasure of personal data, player is kept
+-- pseudonymized so results of game sessions are not affected
+--
+
+ALTER TABLE `players_cache` ADD COLUMN `erased_at` INTEGER NULL;
+
+-- names of players without gdpr consent are not searchable, terms are rebuilt on application start
+UPDATE `players_cache` SET `search_terms` = '' WHERE `is_gdpr_agreed` = 0;
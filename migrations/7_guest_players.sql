@@ -0,0 +1,6 @@
+--
+-- guests are players created on the fly when game session is created (walk-ins at the venue),
+-- is_guest_listed decides whether guests are listed on the public leaderboard of the ranking
+--
+
+ALTER TABLE `rankings_cache` ADD COLUMN `is_guest_listed` INTEGER NOT NULL DEFAULT 0;
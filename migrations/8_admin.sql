@@ -0,0 +1,8 @@
+--
+-- users allowed to perform administrative operations, managed directly in the database
+--
+
+CREATE TABLE `user_admin` (
+    `user_uid` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `created_at` INTEGER NOT NULL
+);
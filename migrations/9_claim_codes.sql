@@ -0,0 +1,24 @@
+--
+-- one-time codes generated by an organiser, redeeming a code links user to the player
+--
+
+CREATE TABLE `player_claim_codes` (
+    `code` TEXT PRIMARY KEY NOT NULL COLLATE BINARY,
+    `player_uuid` TEXT NOT NULL COLLATE BINARY,
+    `creator_uid` TEXT NOT NULL COLLATE BINARY,
+    `expires_at` INTEGER NOT NULL,
+    `redeemed_at` INTEGER NULL,
+    `redeemed_by` TEXT NULL COLLATE BINARY,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE INDEX `player_claim_codes_player_uuid_idx` ON `player_claim_codes` (`player_uuid`);
+
+-- every redeem attempt is stored so guessing codes can be rate limited
+CREATE TABLE `user_claim_attempts` (
+    `user_uid` TEXT NOT NULL COLLATE BINARY,
+    `is_success` INTEGER NOT NULL,
+    `created_at` INTEGER NOT NULL
+);
+
+CREATE INDEX `user_claim_attempts_user_uid_idx` ON `user_claim_attempts` (`user_uid`, `created_at`);
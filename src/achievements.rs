@@ -0,0 +1,306 @@
+use axum::{extract::Path, response::IntoResponse, routing::get, Json, Router};
+use hashbrown::{HashMap, HashSet};
+use serde_json::json;
+
+use crate::{
+    app::AppError,
+    db::DatabaseConnection,
+    firebase,
+    scoring::{GameOutcome, HandKind},
+    users,
+};
+
+/// Condition of the achievement, evaluated after every computed game of the player
+/// (unranked games included) in order games ended
+#[derive(Debug, Clone, Copy)]
+pub enum Rule {
+    GamesPlayed(i64),
+    /// `length` games in a row finished on `placement`
+    PlacementStreak { placement: u8, length: usize },
+    /// won hands worth at least yakuman
+    Yakuman(i64),
+    /// `length` hands in a row won by the player, each worth at least `basic_points`
+    HandValueStreak { basic_points: i64, length: usize },
+    DistinctPlaces(usize),
+}
+
+pub struct Achievement {
+    pub key: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub rule: Rule,
+}
+
+/// Every achievement which can be awarded, keys are stored with awarded achievements
+/// so they must not be changed once released
+pub const CATALOGUE: &[Achievement] = &[
+    Achievement {
+        key: "first_game",
+        name: "First steps",
+        description: "Play the first game",
+        rule: Rule::GamesPlayed(1),
+    },
+    Achievement {
+        key: "games_100",
+        name: "Regular",
+        description: "Play 100 games",
+        rule: Rule::GamesPlayed(100),
+    },
+    Achievement {
+        key: "games_500",
+        name: "Veteran",
+        description: "Play 500 games",
+        rule: Rule::GamesPlayed(500),
+    },
+    Achievement {
+        key: "first_yakuman",
+        name: "Yakuman",
+        description: "Win a hand worth yakuman",
+        rule: Rule::Yakuman(1),
+    },
+    Achievement {
+        key: "top_streak_3",
+        name: "Hat trick",
+        description: "Finish three games in a row on the first place",
+        rule: Rule::PlacementStreak { placement: 1, length: 3 },
+    },
+    Achievement {
+        key: "top_streak_10",
+        name: "Unstoppable",
+        description: "Finish ten games in a row on the first place",
+        rule: Rule::PlacementStreak { placement: 1, length: 10 },
+    },
+    Achievement {
+        key: "mangan_streak_3",
+        name: "Heavy hitter",
+        description: "Win three hands in a row, each worth at least mangan",
+        rule: Rule::HandValueStreak { basic_points: 2000, length: 3 },
+    },
+    Achievement {
+        key: "places_5",
+        name: "Traveller",
+        description: "Play at five different places",
+        rule: Rule::DistinctPlaces(5),
+    },
+];
+
+/// History of a player needed to evaluate the rules
+#[derive(Debug, Default)]
+pub struct PlayerAchievements {
+    games: i64,
+    placements: Vec<u8>,
+    // basic points of every hand played, 0 for hands the player didn't win
+    hands: Vec<i64>,
+    yakuman: i64,
+    places: HashSet<String>,
+    pub awarded: Vec<(&'static str, String, i64)>,
+}
+
+impl PlayerAchievements {
+    pub fn add_game(&mut self, game_session_uuid: &str, place_uuid: &str, seat: usize, outcome: &GameOutcome) {
+        self.games += 1;
+        self.placements.push(outcome.placements[seat]);
+        self.places.insert(place_uuid.to_string());
+
+        // chonbo hand is replayed, so it is not counted as a hand played
+        for hand in outcome.hands.iter().filter(|hand| hand.kind != HandKind::Chonbo) {
+            let basic = hand
+                .wins
+                .iter()
+                .find(|win| win.seat == seat)
+                .map_or(0, |win| win.basic);
+
+            if basic >= 8000 {
+                self.yakuman += 1;
+            }
+
+            self.hands.push(basic);
+        }
+
+        for achievement in CATALOGUE {
+            let is_awarded = self.awarded.iter().any(|(key, _, _)| *key == achievement.key);
+
+            if !is_awarded && self.is_met(&achievement.rule) {
+                self.awarded
+                    .push((achievement.key, game_session_uuid.to_string(), outcome.ended_at));
+            }
+        }
+    }
+
+    fn is_met(&self, rule: &Rule) -> bool {
+        match *rule {
+            Rule::GamesPlayed(games) => self.games >= games,
+            Rule::PlacementStreak { placement, length } => {
+                self.placements.len() >= length
+                    && self.placements.iter().rev().take(length).all(|&p| p == placement)
+            }
+            Rule::Yakuman(count) => self.yakuman >= count,
+            Rule::HandValueStreak { basic_points, length } => {
+                self.hands.len() >= length
+                    && self.hands.iter().rev().take(length).all(|&basic| basic >= basic_points)
+            }
+            Rule::DistinctPlaces(count) => self.places.len() >= count,
+        }
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/rankings/:ranking_uuid/achievements",
+            get(achievements_index),
+        )
+        .route(
+            "/rankings/:ranking_uuid/players/:player_uuid/achievements",
+            get(achievements_player),
+        )
+}
+
+/// Catalogue with number of players in the ranking who were awarded every achievement
+pub async fn achievements_index(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let data = sqlx::query!(
+        r#"SELECT achievement_key, COUNT(*) as "players_count!: i64", MIN(awarded_at) as "first_awarded_at?: i64"
+        FROM player_achievements_cache
+        WHERE ranking_uuid = ?
+        GROUP BY achievement_key"#,
+        ranking_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let awarded = data
+        .iter()
+        .map(|row| (row.achievement_key.as_str(), (row.players_count, row.first_awarded_at)))
+        .collect::<HashMap<_, _>>();
+
+    Ok(Json(json!({
+        "items": CATALOGUE.iter().map(|achievement| {
+            let (players_count, first_awarded_at) = awarded
+                .get(achievement.key)
+                .copied()
+                .unwrap_or((0, None));
+
+            json!({
+                "key": achievement.key,
+                "name": achievement.name,
+                "description": achievement.description,
+                "players_count": players_count,
+                "first_awarded_at": first_awarded_at,
+            })
+        }).collect::<Vec<_>>(),
+        "count": CATALOGUE.len(),
+    })))
+}
+
+/// Whole catalogue, achievements not awarded to the player yet have `awarded_at` null
+pub async fn achievements_player(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path((ranking_uuid, player_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    sqlx::query_scalar!(
+        "SELECT 1 FROM players_cache WHERE ranking_uuid = ? AND uuid = ?",
+        ranking_uuid,
+        player_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::PlayerNotFound)?;
+
+    let data = sqlx::query!(
+        "SELECT achievement_key, game_session_uuid, awarded_at
+        FROM player_achievements_cache
+        WHERE ranking_uuid = ? AND player_uuid = ?",
+        ranking_uuid,
+        player_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let awarded = data
+        .iter()
+        .map(|row| (row.achievement_key.as_str(), row))
+        .collect::<HashMap<_, _>>();
+
+    Ok(Json(json!({
+        "items": CATALOGUE.iter().map(|achievement| {
+            let award = awarded.get(achievement.key);
+
+            json!({
+                "key": achievement.key,
+                "name": achievement.name,
+                "description": achievement.description,
+                "game_session_uuid": award.map(|row| &row.game_session_uuid),
+                "awarded_at": award.map(|row| row.awarded_at),
+            })
+        }).collect::<Vec<_>>(),
+        "count": CATALOGUE.len(),
+        "awarded_count": data.len(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::{Hand, HandWin};
+
+    fn win(seat: usize, basic: i64) -> Hand {
+        Hand {
+            kind: HandKind::Ron,
+            wins: vec![HandWin { seat, value: basic * 4, basic }],
+            loser: Some((seat + 1) % 4),
+            riichi: Vec::new(),
+        }
+    }
+
+    fn outcome(hands: Vec<Hand>) -> GameOutcome {
+        GameOutcome {
+            points: [25000; 4],
+            placements: [1, 2, 3, 4],
+            hands,
+            round: 1,
+            wind: 0,
+            started_at: 0,
+            ended_at: 0,
+        }
+    }
+
+    fn is_awarded(achievements: &PlayerAchievements, key: &str) -> bool {
+        achievements.awarded.iter().any(|(awarded, _, _)| *awarded == key)
+    }
+
+    #[test]
+    fn hand_value_streak_breaks_on_lost_hand() {
+        let mut achievements = PlayerAchievements::default();
+
+        let first = outcome(vec![win(0, 2000), win(0, 3000), win(2, 1000)]);
+        achievements.add_game("game-1", "place-1", 0, &first);
+        achievements.add_game("game-2", "place-1", 0, &outcome(vec![win(0, 2000)]));
+
+        assert!(!is_awarded(&achievements, "mangan_streak_3"));
+
+        achievements.add_game("game-3", "place-1", 0, &outcome(vec![win(0, 2000), win(0, 4000)]));
+
+        assert!(is_awarded(&achievements, "mangan_streak_3"));
+    }
+
+    #[test]
+    fn hand_value_streak_breaks_on_cheap_win() {
+        let mut achievements = PlayerAchievements::default();
+
+        let game = outcome(vec![win(0, 2000), win(0, 1920), win(0, 2000), win(0, 2000)]);
+        achievements.add_game("game-1", "place-1", 0, &game);
+
+        assert!(!is_awarded(&achievements, "mangan_streak_3"));
+    }
+}
@@ -0,0 +1,671 @@
+use axum::{extract::Path, response::IntoResponse, routing::{get, post}, Extension, Json, Router};
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::Connection;
+use validator::{Validate, ValidationError};
+
+use crate::{
+    app::AppError,
+    compute::SharedComputeService,
+    db::DatabaseConnection,
+    firebase, places,
+    sync::SharedSyncService,
+    users,
+    validate::ValidatedJson,
+};
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/admin/players/merge",
+            post(admin_players_merge),
+        )
+        .route(
+            "/admin/players/:player_uuid/claim_codes",
+            post(admin_players_claim_codes_create),
+        )
+        .route(
+            "/admin/players/:player_uuid/unlink",
+            post(admin_players_unlink),
+        )
+        .route(
+            "/admin/sync",
+            post(admin_sync),
+        )
+        .route(
+            "/admin/sync/reports",
+            get(admin_sync_reports),
+        )
+        .route(
+            "/admin/places/pending",
+            get(admin_places_pending),
+        )
+        .route(
+            "/admin/places/:place_uuid/approve",
+            post(admin_places_approve),
+        )
+        .route(
+            "/admin/places/:place_uuid/reject",
+            post(admin_places_reject),
+        )
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_admin_players_merge_input"))]
+pub struct AdminPlayersMerge {
+    // guest merged into target and removed afterwards, federation players would be
+    // restored by the next upstream sync
+    #[validate(length(equal = 36))]
+    source_player_uuid: String,
+    #[validate(length(equal = 36))]
+    target_player_uuid: String,
+    #[serde(default)]
+    is_dry_run: bool,
+}
+
+fn validate_admin_players_merge_input(input: &AdminPlayersMerge) -> Result<(), ValidationError> {
+    if input.source_player_uuid == input.target_player_uuid {
+        Err(ValidationError::new("players must be distinct"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Moves everything referencing source guest (games, users, claim codes, tournaments, leagues,
+/// teams) to target player, with `is_dry_run` affected rows are reported and nothing is changed
+pub async fn admin_players_merge(
+    _claims: firebase::FirebaseClaims,
+    current_admin: users::CurrentAdmin,
+    Extension(compute): Extension<SharedComputeService>,
+    ValidatedJson(input): ValidatedJson<AdminPlayersMerge>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let source = input.source_player_uuid.as_str();
+    let target = input.target_player_uuid.as_str();
+
+    let mut tx = conn.begin().await?;
+
+    let players = sqlx::query!(
+        r#"SELECT s.ranking_uuid, s.is_guest as "is_guest: bool" FROM players_cache s
+        INNER JOIN players_cache t ON t.ranking_uuid = s.ranking_uuid
+        WHERE s.uuid = ? AND t.uuid = ?"#,
+        source,
+        target,
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or(AppError::PlayerNotFound)?;
+    let ranking_uuid = players.ranking_uuid;
+
+    if !players.is_guest {
+        return Err(AppError::PlayersMergeSourceNotGuest);
+    }
+
+    // player can't be seated twice at the same table
+    let shared = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM game_sessions
+        WHERE ?1 IN (player1_uuid, player2_uuid, player3_uuid, player4_uuid)
+        AND ?2 IN (player1_uuid, player2_uuid, player3_uuid, player4_uuid)",
+        source,
+        target,
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    // registrations are unique per tournament and both players have their own standings,
+    // substitutes are either left out of standings or seated against registered players
+    let shared_tournaments = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM (
+            SELECT tournament_uuid FROM tournament_players WHERE player_uuid = ?1
+            UNION SELECT tournament_uuid FROM tournament_substitutes WHERE player_uuid = ?1
+        ) s
+        INNER JOIN (
+            SELECT tournament_uuid FROM tournament_players WHERE player_uuid = ?2
+            UNION SELECT tournament_uuid FROM tournament_substitutes WHERE player_uuid = ?2
+        ) t ON t.tournament_uuid = s.tournament_uuid",
+        source,
+        target,
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    // player enters a league only once
+    let shared_leagues = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM league_entrant_players s
+        INNER JOIN league_entrant_players t ON t.league_uuid = s.league_uuid AND t.player_uuid = ?2
+        WHERE s.player_uuid = ?1",
+        source,
+        target,
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    // player is member of one team of a tournament or ranking at a time
+    let shared_teams = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM team_members s
+        INNER JOIN teams st ON st.uuid = s.team_uuid
+        INNER JOIN team_members t ON t.player_uuid = ?2
+            AND t.joined_at < COALESCE(s.left_at, 9223372036854775807)
+            AND s.joined_at < COALESCE(t.left_at, 9223372036854775807)
+        INNER JOIN teams tt ON tt.uuid = t.team_uuid
+            AND tt.ranking_uuid = st.ranking_uuid AND tt.tournament_uuid IS st.tournament_uuid
+        WHERE s.player_uuid = ?1",
+        source,
+        target,
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    if shared > 0 || shared_tournaments > 0 || shared_leagues > 0 || shared_teams > 0 {
+        return Err(AppError::PlayersMergeConflict);
+    }
+
+    let game_sessions = sqlx::query_scalar!(
+        "SELECT uuid FROM game_sessions
+        WHERE ?1 IN (creator_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid)
+        ORDER BY created_at ASC",
+        source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    // uuids are matched with surrounding quotes, so only whole json strings are replaced
+    let quoted_source = format!("\"{}\"", source);
+    let quoted_target = format!("\"{}\"", target);
+
+    let game_session_events = sqlx::query_scalar!(
+        "SELECT uuid FROM game_session_events
+        WHERE creator_uuid = ?1 OR instr(event_data, ?2) > 0
+        ORDER BY created_at ASC",
+        source,
+        quoted_source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    let user_player = sqlx::query_scalar!(
+        "SELECT user_uid FROM user_player WHERE player_uuid = ?",
+        source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    // unused codes of the guest keep working, they link the user to the target instead
+    let player_claim_codes = sqlx::query_scalar!(
+        "SELECT code FROM player_claim_codes
+        WHERE player_uuid = ? AND redeemed_at IS NULL AND expires_at > strftime('%s', 'now')
+        ORDER BY created_at ASC",
+        source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    let tournament_players = sqlx::query_scalar!(
+        "SELECT tournament_uuid FROM tournament_players WHERE player_uuid = ? ORDER BY created_at ASC",
+        source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    let tournament_byes = sqlx::query_scalar!(
+        "SELECT tournament_round_uuid FROM tournament_byes WHERE player_uuid = ? ORDER BY created_at ASC",
+        source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    let tournament_substitutes = sqlx::query_scalar!(
+        "SELECT tournament_uuid FROM tournament_substitutes WHERE player_uuid = ? ORDER BY created_at ASC",
+        source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    let league_entrant_players = sqlx::query_scalar!(
+        "SELECT league_uuid FROM league_entrant_players WHERE player_uuid = ? ORDER BY created_at ASC",
+        source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    let team_members = sqlx::query_scalar!(
+        "SELECT team_uuid FROM team_members WHERE player_uuid = ? ORDER BY joined_at ASC",
+        source,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    if !input.is_dry_run {
+        sqlx::query!(
+            "UPDATE game_sessions SET
+                creator_uuid = CASE WHEN creator_uuid = ?1 THEN ?2 ELSE creator_uuid END,
+                player1_uuid = CASE WHEN player1_uuid = ?1 THEN ?2 ELSE player1_uuid END,
+                player2_uuid = CASE WHEN player2_uuid = ?1 THEN ?2 ELSE player2_uuid END,
+                player3_uuid = CASE WHEN player3_uuid = ?1 THEN ?2 ELSE player3_uuid END,
+                player4_uuid = CASE WHEN player4_uuid = ?1 THEN ?2 ELSE player4_uuid END,
+                is_not_computed = 1
+            WHERE ?1 IN (creator_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid)",
+            source,
+            target,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE game_session_events SET
+                creator_uuid = CASE WHEN creator_uuid = ?1 THEN ?2 ELSE creator_uuid END,
+                event_data = replace(event_data, ?3, ?4)
+            WHERE creator_uuid = ?1 OR instr(event_data, ?3) > 0",
+            source,
+            target,
+            quoted_source,
+            quoted_target,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE user_player SET player_uuid = ? WHERE player_uuid = ?",
+            target,
+            source,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE player_claim_codes SET player_uuid = ? WHERE player_uuid = ? AND redeemed_at IS NULL",
+            target,
+            source,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE tournament_players SET player_uuid = ? WHERE player_uuid = ?",
+            target,
+            source,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE tournament_byes SET player_uuid = ? WHERE player_uuid = ?",
+            target,
+            source,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE tournament_substitutes SET player_uuid = ? WHERE player_uuid = ?",
+            target,
+            source,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE league_entrant_players SET player_uuid = ? WHERE player_uuid = ?",
+            target,
+            source,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE team_members SET player_uuid = ? WHERE player_uuid = ?",
+            target,
+            source,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!("DELETE FROM players_cache WHERE uuid = ?", source)
+            .execute(&mut tx)
+            .await?;
+
+        // ranking is computed from scratch, so whole ranking has to be recomputed
+        sqlx::query!(
+            "UPDATE rankings_cache SET computed_at = NULL WHERE uuid = ?",
+            ranking_uuid,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        tracing::info!(
+            "admin [{}] merged player [{}] into [{}]",
+            current_admin.user_uid,
+            source,
+            target
+        );
+
+        compute.request_recompute();
+    }
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "source_player_uuid": source,
+                "target_player_uuid": target,
+                "ranking_uuid": ranking_uuid,
+                "is_dry_run": input.is_dry_run,
+                "$game_sessions": json!({
+                    "items": game_sessions,
+                    "count": game_sessions.len(),
+                }),
+                "$game_session_events": json!({
+                    "items": game_session_events,
+                    "count": game_session_events.len(),
+                }),
+                "$user_player": json!({
+                    "items": user_player,
+                    "count": user_player.len(),
+                }),
+                "$player_claim_codes": json!({
+                    "items": player_claim_codes,
+                    "count": player_claim_codes.len(),
+                }),
+                "$tournament_players": json!({
+                    "items": tournament_players,
+                    "count": tournament_players.len(),
+                }),
+                "$tournament_byes": json!({
+                    "items": tournament_byes,
+                    "count": tournament_byes.len(),
+                }),
+                "$tournament_substitutes": json!({
+                    "items": tournament_substitutes,
+                    "count": tournament_substitutes.len(),
+                }),
+                "$league_entrant_players": json!({
+                    "items": league_entrant_players,
+                    "count": league_entrant_players.len(),
+                }),
+                "$team_members": json!({
+                    "items": team_members,
+                    "count": team_members.len(),
+                }),
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+const CLAIM_CODE_LENGTH: usize = 10;
+const CLAIM_CODE_TTL: i64 = 7 * 24 * 60 * 60;
+// without letters and digits easily confused with each other (0/O, 1/I/L)
+const CLAIM_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+
+/// Generates one-time code which links the user redeeming it to the player
+pub async fn admin_players_claim_codes_create(
+    _claims: firebase::FirebaseClaims,
+    current_admin: users::CurrentAdmin,
+    Path(player_uuid): Path<String>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    sqlx::query_scalar!("SELECT 1 FROM players_cache WHERE uuid = ?", player_uuid)
+        .fetch_optional(&mut conn)
+        .await?
+        .ok_or(AppError::PlayerNotFound)?;
+
+    let code = {
+        let mut rng = rand::thread_rng();
+
+        (0..CLAIM_CODE_LENGTH)
+            .map(|_| CLAIM_CODE_ALPHABET[rng.gen_range(0..CLAIM_CODE_ALPHABET.len())] as char)
+            .collect::<String>()
+    };
+
+    let expires_at = sqlx::query_scalar!(
+        "INSERT INTO player_claim_codes (code, player_uuid, creator_uid, expires_at, created_at)
+        VALUES (?1, ?2, ?3, strftime('%s', 'now') + ?4, strftime('%s', 'now'))
+        RETURNING expires_at",
+        code,
+        player_uuid,
+        current_admin.user_uid,
+        CLAIM_CODE_TTL,
+    )
+    .fetch_one(&mut conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "code": code,
+                "player_uuid": player_uuid,
+                "expires_at": expires_at,
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+/// Unlinks every user from the player, unused claim codes of the player are invalidated
+pub async fn admin_players_unlink(
+    _claims: firebase::FirebaseClaims,
+    current_admin: users::CurrentAdmin,
+    Path(player_uuid): Path<String>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let mut tx = conn.begin().await?;
+
+    let users_uids = sqlx::query_scalar!(
+        "DELETE FROM user_player WHERE player_uuid = ? RETURNING user_uid",
+        player_uuid,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE player_claim_codes SET expires_at = strftime('%s', 'now')
+        WHERE player_uuid = ? AND redeemed_at IS NULL AND expires_at > strftime('%s', 'now')",
+        player_uuid,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    tracing::info!(
+        "admin [{}] unlinked users {:?} from player [{}]",
+        current_admin.user_uid,
+        users_uids,
+        player_uuid
+    );
+
+    Ok(Json(json!({
+        "items": users_uids,
+        "count": users_uids.len(),
+    })))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct AdminSync {
+    #[serde(default)]
+    is_dry_run: bool,
+}
+
+/// Runs upstream sync on demand, with `is_dry_run` only the diff is reported
+pub async fn admin_sync(
+    _claims: firebase::FirebaseClaims,
+    _current_admin: users::CurrentAdmin,
+    Extension(sync): Extension<SharedSyncService>,
+    ValidatedJson(input): ValidatedJson<AdminSync>,
+) -> Result<impl IntoResponse, AppError> {
+    if !sync.is_enabled() {
+        return Err(AppError::SyncNotConfigured);
+    }
+
+    let report = sync
+        .sync(input.is_dry_run)
+        .await
+        .map_err(|err| AppError::Unknown(Some(err.into())))?;
+
+    Ok(Json(json!({
+        "items": vec![report],
+        "count": 1,
+    })))
+}
+
+pub async fn admin_sync_reports(
+    _claims: firebase::FirebaseClaims,
+    _current_admin: users::CurrentAdmin,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let data = sqlx::query!(
+        r#"SELECT
+            uuid, source, is_dry_run as "is_dry_run: bool", counts, rejected_players_uuids, error,
+            started_at, finished_at
+        FROM sync_reports ORDER BY started_at DESC LIMIT 20"#
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": data.iter().map(|row| {
+            json!({
+                "uuid": row.uuid,
+                "source": row.source,
+                "is_dry_run": row.is_dry_run,
+                "counts": row.counts.as_deref().and_then(|counts| serde_json::from_str::<serde_json::Value>(counts).ok()),
+                "rejected_players_uuids": row.rejected_players_uuids.as_deref()
+                    .and_then(|uuids| serde_json::from_str::<Vec<String>>(uuids).ok())
+                    .unwrap_or_default(),
+                "error": row.error,
+                "started_at": row.started_at,
+                "finished_at": row.finished_at,
+            })
+        }).collect::<Vec<_>>(),
+        "count": data.len(),
+    })))
+}
+
+/// Moderation queue, oldest submissions first. places of unknown type are listed too,
+/// until an admin updates their type
+pub async fn admin_places_pending(
+    _claims: firebase::FirebaseClaims,
+    _current_admin: users::CurrentAdmin,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let data = sqlx::query!(
+        r#"SELECT uuid, name, street, city, country_code, type as "place_type!", status, creator_uid, created_at
+        FROM places
+        WHERE (status = ? OR type = ?) AND archived_at IS NULL
+        ORDER BY created_at ASC"#,
+        places::PLACE_STATUS_PENDING,
+        places::PLACE_TYPE_UNKNOWN,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": data.iter().map(|row| {
+            json!({
+                "uuid": row.uuid,
+                "name": row.name,
+                "street": row.street,
+                "city": row.city,
+                "country_code": row.country_code,
+                "type": row.place_type,
+                "status": row.status,
+                "creator_uid": row.creator_uid,
+                "created_at": row.created_at,
+            })
+        }).collect::<Vec<_>>(),
+        "count": data.len(),
+    })))
+}
+
+pub async fn admin_places_approve(
+    _claims: firebase::FirebaseClaims,
+    current_admin: users::CurrentAdmin,
+    Path(place_uuid): Path<String>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    moderate_place(&mut conn, &place_uuid, places::PLACE_STATUS_APPROVED, None, &current_admin).await?;
+
+    places::places_show(&mut conn, &place_uuid).await
+}
+
+#[derive(Deserialize, Validate)]
+pub struct AdminPlacesReject {
+    // shown to the user who submitted the place
+    #[validate(length(min = 1, max = 256))]
+    reason: Option<String>,
+}
+
+pub async fn admin_places_reject(
+    _claims: firebase::FirebaseClaims,
+    current_admin: users::CurrentAdmin,
+    Path(place_uuid): Path<String>,
+    ValidatedJson(input): ValidatedJson<AdminPlacesReject>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    moderate_place(
+        &mut conn,
+        &place_uuid,
+        places::PLACE_STATUS_REJECTED,
+        input.reason.as_deref(),
+        &current_admin,
+    )
+    .await?;
+
+    places::places_show(&mut conn, &place_uuid).await
+}
+
+async fn moderate_place(
+    conn: &mut sqlx::SqliteConnection,
+    place_uuid: &str,
+    status: &str,
+    rejection_reason: Option<&str>,
+    current_admin: &users::CurrentAdmin,
+) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        "UPDATE places SET
+            status = ?, rejection_reason = ?, moderator_uid = ?, moderated_at = strftime('%s', 'now')
+        WHERE uuid = ?",
+        status,
+        rejection_reason,
+        current_admin.user_uid,
+        place_uuid,
+    )
+    .execute(&mut *conn)
+    .await;
+
+    // approving a rejected place takes its name back, which may be used meanwhile
+    let done = match result {
+        Ok(done) => done,
+        Err(err) => {
+            let name = sqlx::query_scalar!("SELECT name FROM places WHERE uuid = ?", place_uuid)
+                .fetch_one(&mut *conn)
+                .await?;
+
+            return Err(places::name_conflict(conn, &name, err).await);
+        }
+    };
+
+    done.rows_affected()
+        .eq(&1)
+        .then_some(())
+        .ok_or(AppError::PlaceNotFound)?;
+
+    tracing::info!("admin [{}] marked place [{}] as {}", current_admin.user_uid, place_uuid, status);
+
+    Ok(())
+}
@@ -21,6 +21,36 @@ pub enum AppError {
     GameAlreadyStarted,
     GameAlreadyEnded,
     GameAlreadyUndone,
+    PlayerNotFound,
+    PlayersMergeConflict,
+    PlayersMergeSourceNotGuest,
+    UserAlreadyAssigned,
+    ClaimCodeInvalid,
+    TooManyRequests,
+    SyncNotConfigured,
+    PlaceNotFound,
+    PlaceNameConflict(String),
+    RankingNotFound,
+    GameSessionNotFound,
+    TournamentNotFound,
+    TournamentRoundNotFound,
+    TournamentRegistrationClosed,
+    TournamentFinished,
+    TournamentRoundsExceeded,
+    TournamentPlayerNotCheckedIn,
+    TournamentNotEnoughPlayers,
+    TournamentNotFinished,
+    TournamentPlayersMissingEmaId(Vec<String>),
+    GameSessionAlreadyLinked,
+    LeagueNotFound,
+    LeagueFixtureNotFound,
+    LeaguePlayerAlreadyEntered,
+    LeagueNotEnoughEntrants,
+    LeagueFixturePlayersMismatch,
+    TeamNotFound,
+    TeamNameConflict,
+    TeamFull,
+    TeamPlayerAlreadyMember,
     SqlError(sqlx::Error),
     Unknown(Option<Box<dyn std::error::Error>>),
 }
@@ -44,6 +74,36 @@ impl std::error::Error for AppError {
             AppError::GameAlreadyStarted => None,
             AppError::GameAlreadyEnded => None,
             AppError::GameAlreadyUndone => None,
+            AppError::PlayerNotFound => None,
+            AppError::PlayersMergeConflict => None,
+            AppError::PlayersMergeSourceNotGuest => None,
+            AppError::UserAlreadyAssigned => None,
+            AppError::ClaimCodeInvalid => None,
+            AppError::TooManyRequests => None,
+            AppError::SyncNotConfigured => None,
+            AppError::PlaceNotFound => None,
+            AppError::PlaceNameConflict(_) => None,
+            AppError::RankingNotFound => None,
+            AppError::GameSessionNotFound => None,
+            AppError::TournamentNotFound => None,
+            AppError::TournamentRoundNotFound => None,
+            AppError::TournamentRegistrationClosed => None,
+            AppError::TournamentFinished => None,
+            AppError::TournamentRoundsExceeded => None,
+            AppError::TournamentPlayerNotCheckedIn => None,
+            AppError::TournamentNotEnoughPlayers => None,
+            AppError::TournamentNotFinished => None,
+            AppError::TournamentPlayersMissingEmaId(_) => None,
+            AppError::GameSessionAlreadyLinked => None,
+            AppError::LeagueNotFound => None,
+            AppError::LeagueFixtureNotFound => None,
+            AppError::LeaguePlayerAlreadyEntered => None,
+            AppError::LeagueNotEnoughEntrants => None,
+            AppError::LeagueFixturePlayersMismatch => None,
+            AppError::TeamNotFound => None,
+            AppError::TeamNameConflict => None,
+            AppError::TeamFull => None,
+            AppError::TeamPlayerAlreadyMember => None,
             AppError::SqlError(err) => Some(err),
             AppError::Unknown(err) => err.as_ref().map(|err| err.as_ref()),
         }
@@ -68,6 +128,12 @@ impl From<BytesRejection> for AppError {
     }
 }
 
+impl From<csv::Error> for AppError {
+    fn from(inner: csv::Error) -> Self {
+        AppError::Unknown(Some(inner.into()))
+    }
+}
+
 impl From<serde_json::Error> for AppError {
     fn from(inner: serde_json::Error) -> Self {
         AppError::InvalidJsonSyntax(inner)
@@ -163,6 +229,189 @@ impl IntoResponse for AppError {
                     "error": "game already undone",
                 })),
             ),
+            AppError::PlayerNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "player not found",
+                })),
+            ),
+            AppError::PlayersMergeConflict => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "players took part in the same game session, tournament or league, or in teams at the same time",
+                })),
+            ),
+            AppError::PlayersMergeSourceNotGuest => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "only guest players can be merged into another player",
+                })),
+            ),
+            AppError::UserAlreadyAssigned => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "user already assigned to player",
+                })),
+            ),
+            AppError::ClaimCodeInvalid => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "claim code invalid or expired",
+                })),
+            ),
+            AppError::TooManyRequests => (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(json!({
+                    "error": "too many requests",
+                })),
+            ),
+            AppError::SyncNotConfigured => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "sync source is not configured",
+                })),
+            ),
+            AppError::PlaceNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "place not found",
+                })),
+            ),
+            AppError::PlaceNameConflict(place_uuid) => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "place name already taken",
+                    "field": "name",
+                    "place_uuid": place_uuid,
+                })),
+            ),
+            AppError::RankingNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "ranking not found",
+                })),
+            ),
+            AppError::GameSessionNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "game session not found",
+                })),
+            ),
+            AppError::TournamentNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "tournament not found",
+                })),
+            ),
+            AppError::TournamentRoundNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "tournament round not found",
+                })),
+            ),
+            AppError::TournamentRegistrationClosed => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "tournament registration closed",
+                })),
+            ),
+            AppError::TournamentFinished => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "tournament already finished",
+                })),
+            ),
+            AppError::TournamentRoundsExceeded => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "all tournament rounds already created",
+                })),
+            ),
+            AppError::TournamentPlayerNotCheckedIn => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "player not checked in to tournament",
+                })),
+            ),
+            AppError::TournamentNotEnoughPlayers => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "not enough checked-in players to seat a table",
+                })),
+            ),
+            AppError::TournamentNotFinished => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "tournament not finished yet",
+                })),
+            ),
+            AppError::TournamentPlayersMissingEmaId(players_uuids) => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "players without valid EMA id",
+                    "players_uuids": players_uuids,
+                })),
+            ),
+            AppError::GameSessionAlreadyLinked => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "game session or its player already linked to tournament round",
+                })),
+            ),
+            AppError::LeagueNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "league not found",
+                })),
+            ),
+            AppError::LeagueFixtureNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "league fixture not found",
+                })),
+            ),
+            AppError::LeaguePlayerAlreadyEntered => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "player already entered in league",
+                })),
+            ),
+            AppError::LeagueNotEnoughEntrants => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "not enough league entrants to make a fixture",
+                })),
+            ),
+            AppError::LeagueFixturePlayersMismatch => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "players don't represent entrants of league fixture",
+                })),
+            ),
+            AppError::TeamNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "team not found",
+                })),
+            ),
+            AppError::TeamNameConflict => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "team name already taken",
+                })),
+            ),
+            AppError::TeamFull => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "team already has four members",
+                })),
+            ),
+            AppError::TeamPlayerAlreadyMember => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "player already member of a competing team",
+                })),
+            ),
         }
         .into_response()
     }
@@ -0,0 +1,690 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use hashbrown::{HashMap, HashSet};
+use sqlx::{pool::PoolConnection, Sqlite, SqlitePool};
+use tokio::sync::Notify;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    achievements::PlayerAchievements,
+    rating::{Rating, RatingAlgorithm, RatingAlgorithmKind},
+    scoring::{self, GameEvent, GameOutcome},
+    stats::PlayerStats,
+};
+
+/// Rank points gained for 1st, 2nd, 3rd and 4th placement
+pub const PLACEMENT_RANK_POINTS: [i64; 4] = [3, 1, 0, 0];
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+pub type SharedComputeService = Arc<ComputeService>;
+
+pub struct ComputeService {
+    pool: SqlitePool,
+    notify: Notify,
+}
+
+/// Per-ranking rules of computation - rating weighting
+/// and whether player is listed on the public leaderboard
+pub struct RankingRules {
+    pub algorithm: RatingAlgorithmKind,
+    min_games: i64,
+    min_opponents: i64,
+    inactivity_days: Option<i64>,
+    inactivity_policy: String,
+    inactivity_decay: f64,
+    is_guest_listed: bool,
+    novice_games: i64,
+    novice_friendly_weight: f64,
+    novice_loss_weight: f64,
+}
+
+/// How rating change of a player in a game session was weighted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weighting {
+    Standard,
+    Unranked,
+    NoviceFriendly,
+    NoviceProtected,
+}
+
+impl Weighting {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Weighting::Standard => "standard",
+            Weighting::Unranked => "unranked",
+            Weighting::NoviceFriendly => "novice_friendly",
+            Weighting::NoviceProtected => "novice_protected",
+        }
+    }
+}
+
+/// Computed result of a single player in a game session
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerResult {
+    pub rating_before: Rating,
+    pub rating_after: Rating,
+    pub rank_points: i64,
+    pub weighting: Weighting,
+    pub weight: f64,
+}
+
+#[derive(Default)]
+struct PlayerActivity {
+    games: i64,
+    opponents: HashSet<String>,
+    last_game_at: Option<i64>,
+}
+
+impl RankingRules {
+    pub async fn fetch(conn: &mut sqlx::SqliteConnection, ranking_uuid: &str) -> Result<Self, anyhow::Error> {
+        let ranking = sqlx::query!(
+            r#"SELECT
+                rating_algorithm, min_games, min_opponents,
+                inactivity_days, inactivity_policy, inactivity_decay,
+                is_guest_listed as "is_guest_listed: bool",
+                novice_games, novice_friendly_weight, novice_loss_weight
+            FROM rankings_cache WHERE uuid = ?"#,
+            ranking_uuid
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(Self {
+            algorithm: RatingAlgorithmKind::from_str(&ranking.rating_algorithm)?,
+            min_games: ranking.min_games,
+            min_opponents: ranking.min_opponents,
+            inactivity_days: ranking.inactivity_days,
+            inactivity_policy: ranking.inactivity_policy,
+            inactivity_decay: ranking.inactivity_decay,
+            is_guest_listed: ranking.is_guest_listed,
+            novice_games: ranking.novice_games,
+            novice_friendly_weight: ranking.novice_friendly_weight,
+            novice_loss_weight: ranking.novice_loss_weight,
+        })
+    }
+
+    /// Rates a single game session, `games_played` are counts of ranked games
+    /// played by each player before this game and decide who is a newcomer
+    pub fn rate_game(
+        &self,
+        algorithm: &dyn RatingAlgorithm,
+        before: &[Rating],
+        games_played: &[i64],
+        placements: &[u8],
+        is_unranked: bool,
+        is_novice_friendly: bool,
+    ) -> Vec<PlayerResult> {
+        let after = algorithm.rate(before, placements);
+
+        before
+            .iter()
+            .zip(after.iter())
+            .enumerate()
+            .map(|(i, (before, after))| {
+                let (weighting, weight) = if is_unranked {
+                    (Weighting::Unranked, 0.0)
+                } else if !is_novice_friendly {
+                    (Weighting::Standard, 1.0)
+                } else if games_played[i] >= self.novice_games {
+                    (Weighting::NoviceFriendly, self.novice_friendly_weight)
+                } else if after.value < before.value {
+                    (Weighting::NoviceProtected, self.novice_loss_weight)
+                } else {
+                    (Weighting::Standard, 1.0)
+                };
+                let rank_points = if is_unranked {
+                    0
+                } else {
+                    PLACEMENT_RANK_POINTS[placements[i] as usize - 1]
+                };
+
+                PlayerResult {
+                    rating_before: *before,
+                    rating_after: before.weighted(after, weight),
+                    rank_points,
+                    weighting,
+                    weight,
+                }
+            })
+            .collect()
+    }
+
+    /// Days of inactivity above the allowed limit
+    fn inactive_days(&self, activity: &PlayerActivity, now: i64) -> Option<i64> {
+        let limit = self.inactivity_days?;
+        let days = (now - activity.last_game_at?) / SECONDS_PER_DAY;
+
+        if days > limit {
+            Some(days - limit)
+        } else {
+            None
+        }
+    }
+
+    fn unlisted_reason(&self, activity: &PlayerActivity, is_guest: bool, now: i64) -> Option<String> {
+        let missing_games = self.min_games - activity.games;
+        let missing_opponents = self.min_opponents - activity.opponents.len() as i64;
+
+        if is_guest && !self.is_guest_listed {
+            Some("guest players are not listed".to_string())
+        } else if missing_games > 0 {
+            Some(match missing_games {
+                1 => "1 more game needed".to_string(),
+                n => format!("{} more games needed", n),
+            })
+        } else if missing_opponents > 0 {
+            Some(match missing_opponents {
+                1 => "1 more distinct opponent needed".to_string(),
+                n => format!("{} more distinct opponents needed", n),
+            })
+        } else if self.inactivity_policy == "hide" && self.inactive_days(activity, now).is_some() {
+            Some(format!(
+                "no games for more than {} days",
+                self.inactivity_days.unwrap_or_default()
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Rating lowered by inactivity decay, never below initial rating
+    fn decayed(&self, rating: Rating, initial: Rating, activity: &PlayerActivity, now: i64) -> Rating {
+        match self.inactive_days(activity, now) {
+            Some(days) if self.inactivity_policy == "decay" && rating.value > initial.value => Rating {
+                value: (rating.value - self.inactivity_decay * days as f64).max(initial.value),
+                ..rating
+            },
+            _ => rating,
+        }
+    }
+}
+
+struct ComputedGame {
+    game_session_uuid: String,
+    place_uuid: String,
+    players: [String; 4],
+    is_unranked: bool,
+    is_novice_friendly: bool,
+    outcome: GameOutcome,
+}
+
+impl ComputeService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            notify: Notify::new(),
+        }
+    }
+
+    /// Wakes up compute worker, rankings with dirty game sessions will be recomputed
+    pub fn request_recompute(&self) {
+        self.notify.notify_one();
+    }
+
+    pub async fn wait_for_recompute_request(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.notify.notified()).await;
+    }
+
+    /// Marks game session as requiring computation,
+    /// should be called whenever event affecting game result is stored
+    pub async fn mark_dirty(
+        &self,
+        conn: &mut PoolConnection<Sqlite>,
+        game_session_uuid: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE game_sessions SET is_not_computed = 1 WHERE uuid = ?",
+            game_session_uuid
+        )
+        .execute(conn)
+        .await?;
+
+        self.request_recompute();
+
+        Ok(())
+    }
+
+    /// Recomputes every ranking which was never computed, was computed more than a day ago
+    /// (so inactivity is applied) or has ended / undone game sessions not computed yet
+    pub async fn compute_dirty_rankings(&self) -> Result<(), anyhow::Error> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rankings = sqlx::query_scalar!(
+            r#"SELECT uuid FROM rankings_cache
+            WHERE deleted_at IS NULL AND (computed_at IS NULL
+            OR computed_at < strftime('%s', 'now') - 86400
+            OR EXISTS (
+                SELECT 1 FROM game_sessions gs
+                WHERE gs.ranking_uuid = rankings_cache.uuid
+                AND gs.is_not_computed = 1
+                AND EXISTS (
+                    SELECT 1 FROM game_session_events e
+                    WHERE e.game_session_uuid = gs.uuid
+                    AND e.event_type IN ('end', 'undo_game')
+                )
+            ))"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        drop(conn);
+
+        for ranking_uuid in rankings {
+            if let Err(err) = self.compute_ranking(&ranking_uuid).await {
+                error!("failed to compute ranking [{}]: {}", ranking_uuid, err);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn compute_ranking(&self, ranking_uuid: &str) -> Result<(), anyhow::Error> {
+        info!("start computing ranking [{}]", ranking_uuid);
+
+        let mut tx = self.pool.begin().await?;
+
+        // write first so the transaction holds the lock
+        // and no event stored in the meantime is lost
+        sqlx::query!(
+            "UPDATE game_sessions SET is_not_computed = 0 WHERE ranking_uuid = ?",
+            ranking_uuid
+        )
+        .execute(&mut tx)
+        .await?;
+
+        let rules = RankingRules::fetch(&mut tx, ranking_uuid).await?;
+        let algorithm = rules.algorithm.algorithm();
+
+        let games = load_computed_games(&mut tx, ranking_uuid).await?;
+
+        sqlx::query!(
+            "DELETE FROM game_sessions_stats_cache
+            WHERE game_session_uuid IN (SELECT uuid FROM game_sessions WHERE ranking_uuid = ?)",
+            ranking_uuid
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM game_session_results_cache WHERE ranking_uuid = ?",
+            ranking_uuid
+        )
+        .execute(&mut tx)
+        .await?;
+
+        let mut ratings: HashMap<String, Rating> = HashMap::new();
+        let mut rank_points: HashMap<String, i64> = HashMap::new();
+        let mut activities: HashMap<String, PlayerActivity> = HashMap::new();
+        let mut stats: HashMap<String, PlayerStats> = HashMap::new();
+        let mut achievements: HashMap<String, PlayerAchievements> = HashMap::new();
+
+        for game in &games {
+            let outcome = &game.outcome;
+            let before = game
+                .players
+                .iter()
+                .map(|uuid| *ratings.get(uuid).unwrap_or(&algorithm.initial()))
+                .collect::<Vec<_>>();
+            let games_played = game
+                .players
+                .iter()
+                .map(|uuid| activities.get(uuid).map_or(0, |activity| activity.games))
+                .collect::<Vec<_>>();
+            let results = rules.rate_game(
+                algorithm.as_ref(),
+                &before,
+                &games_played,
+                &outcome.placements,
+                game.is_unranked,
+                game.is_novice_friendly,
+            );
+            let duration = outcome.duration();
+
+            sqlx::query!(
+                "INSERT INTO game_sessions_stats_cache (
+                    game_session_uuid, player1_points, player2_points, player3_points, player4_points,
+                    ended_at, round, wind, duration, created_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))",
+                game.game_session_uuid,
+                outcome.points[0],
+                outcome.points[1],
+                outcome.points[2],
+                outcome.points[3],
+                outcome.ended_at,
+                outcome.round,
+                outcome.wind,
+                duration,
+            )
+            .execute(&mut tx)
+            .await?;
+
+            for (seat, (player_uuid, result)) in game.players.iter().zip(results.iter()).enumerate() {
+                let placement = outcome.placements[seat];
+                let points = outcome.points[seat];
+                let weighting = result.weighting.as_str();
+                let seat = seat as i64;
+
+                sqlx::query!(
+                    "INSERT INTO game_session_results_cache (
+                        game_session_uuid, ranking_uuid, player_uuid, seat, placement, points,
+                        rating_before, rating_after, rating_deviation_after, rank_points,
+                        weight, weighting, ended_at, created_at
+                    )
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))",
+                    game.game_session_uuid,
+                    ranking_uuid,
+                    player_uuid,
+                    seat,
+                    placement,
+                    points,
+                    result.rating_before.value,
+                    result.rating_after.value,
+                    result.rating_after.deviation,
+                    result.rank_points,
+                    result.weight,
+                    weighting,
+                    outcome.ended_at,
+                )
+                .execute(&mut tx)
+                .await?;
+
+                ratings.insert(player_uuid.clone(), result.rating_after);
+                *rank_points.entry(player_uuid.clone()).or_insert(0) += result.rank_points;
+                stats
+                    .entry(player_uuid.clone())
+                    .or_default()
+                    .add_game(&game.game_session_uuid, seat as usize, outcome);
+                achievements
+                    .entry(player_uuid.clone())
+                    .or_default()
+                    .add_game(&game.game_session_uuid, &game.place_uuid, seat as usize, outcome);
+
+                // unranked games only count for statistics
+                if game.is_unranked {
+                    continue;
+                }
+
+                let activity = activities.entry(player_uuid.clone()).or_default();
+                activity.games += 1;
+                activity.last_game_at = Some(outcome.ended_at);
+                activity
+                    .opponents
+                    .extend(game.players.iter().filter(|uuid| *uuid != player_uuid).cloned());
+            }
+        }
+
+        let ranks = sqlx::query!(
+            r#"SELECT uuid, required_points, required_exam as "required_exam: bool"
+            FROM ranks_cache WHERE ranking_uuid = ? AND deleted_at IS NULL ORDER BY required_points DESC"#,
+            ranking_uuid
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        let players = sqlx::query!(
+            r#"SELECT uuid, is_exam_done as "is_exam_done: bool", is_guest as "is_guest: bool"
+            FROM players_cache WHERE ranking_uuid = ? AND deleted_at IS NULL"#,
+            ranking_uuid
+        )
+        .fetch_all(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM ranking_snapshot_cache WHERE ranking_uuid = ?",
+            ranking_uuid
+        )
+        .execute(&mut tx)
+        .await?;
+
+        if ranks.is_empty() {
+            warn!("ranking [{}] has no ranks, skipping snapshot", ranking_uuid);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let no_activity = PlayerActivity::default();
+
+        for player in players.iter().filter(|_| !ranks.is_empty()) {
+            let activity = activities.get(&player.uuid).unwrap_or(&no_activity);
+            let rating = *ratings.get(&player.uuid).unwrap_or(&algorithm.initial());
+            let rating = rules.decayed(rating, algorithm.initial(), activity, now);
+            let unlisted_reason = rules.unlisted_reason(activity, player.is_guest, now);
+            let is_listed = unlisted_reason.is_none();
+            let opponents_count = activity.opponents.len() as i64;
+            let points = *rank_points.get(&player.uuid).unwrap_or(&0);
+            // ranks are ordered from the highest, lowest rank is given to everyone
+            let rank = ranks
+                .iter()
+                .find(|rank| rank.required_points <= points && (!rank.required_exam || player.is_exam_done))
+                .unwrap_or_else(|| ranks.last().expect("ranks are not empty"));
+            let elo_points = rating.value.round() as i64;
+
+            sqlx::query!(
+                "INSERT INTO ranking_snapshot_cache (
+                    ranking_uuid, player_uuid, rank_uuid, rank_points, elo_points,
+                    rating_deviation, rating_volatility, games_count, opponents_count,
+                    last_game_at, is_listed, unlisted_reason
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                ranking_uuid,
+                player.uuid,
+                rank.uuid,
+                points,
+                elo_points,
+                rating.deviation,
+                rating.volatility,
+                activity.games,
+                opponents_count,
+                activity.last_game_at,
+                is_listed,
+                unlisted_reason,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "DELETE FROM player_stats_cache WHERE ranking_uuid = ?",
+            ranking_uuid
+        )
+        .execute(&mut tx)
+        .await?;
+
+        for (player_uuid, stats) in &stats {
+            let average_hand_value = stats.average_hand_value();
+            let (best_game_session_uuid, best_game_points) = stats.best_game.clone().unzip();
+            let (worst_game_session_uuid, worst_game_points) = stats.worst_game.clone().unzip();
+
+            sqlx::query!(
+                "INSERT INTO player_stats_cache (
+                    ranking_uuid, player_uuid, games_count,
+                    placement1_count, placement2_count, placement3_count, placement4_count,
+                    hands_count, wins_count, tsumo_count, ron_count, deal_in_count, riichi_count,
+                    chonbo_count, average_hand_value, best_game_session_uuid, best_game_points,
+                    worst_game_session_uuid, worst_game_points, created_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))",
+                ranking_uuid,
+                player_uuid,
+                stats.games,
+                stats.placements[0],
+                stats.placements[1],
+                stats.placements[2],
+                stats.placements[3],
+                stats.hands,
+                stats.wins,
+                stats.tsumo,
+                stats.ron,
+                stats.deal_ins,
+                stats.riichi,
+                stats.chonbo,
+                average_hand_value,
+                best_game_session_uuid,
+                best_game_points,
+                worst_game_session_uuid,
+                worst_game_points,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "DELETE FROM player_achievements_cache WHERE ranking_uuid = ?",
+            ranking_uuid
+        )
+        .execute(&mut tx)
+        .await?;
+
+        for (player_uuid, player_achievements) in &achievements {
+            for (achievement_key, game_session_uuid, awarded_at) in &player_achievements.awarded {
+                sqlx::query!(
+                    "INSERT INTO player_achievements_cache (
+                        ranking_uuid, player_uuid, achievement_key, game_session_uuid, awarded_at, created_at
+                    )
+                    VALUES (?, ?, ?, ?, ?, strftime('%s', 'now'))",
+                    ranking_uuid,
+                    player_uuid,
+                    achievement_key,
+                    game_session_uuid,
+                    awarded_at,
+                )
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+
+        sqlx::query!(
+            "UPDATE rankings_cache SET computed_at = strftime('%s', 'now') WHERE uuid = ?",
+            ranking_uuid
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        info!("finished computing ranking [{}], {} games computed", ranking_uuid, games.len());
+
+        Ok(())
+    }
+}
+
+/// Current rating and count of ranked games of the players as left by the last computation,
+/// players without computed games get initial rating
+pub async fn current_ratings(
+    conn: &mut sqlx::SqliteConnection,
+    algorithm: &dyn RatingAlgorithm,
+    ranking_uuid: &str,
+    players_uuids: &[String],
+) -> Result<Vec<(Rating, i64)>, sqlx::Error> {
+    let mut ratings = Vec::with_capacity(players_uuids.len());
+
+    for player_uuid in players_uuids {
+        let last_result = sqlx::query!(
+            r#"SELECT rating_after as "rating_after!", rating_deviation_after FROM game_session_results_cache
+            WHERE ranking_uuid = ? AND player_uuid = ?
+            ORDER BY ended_at DESC, rowid DESC
+            LIMIT 1"#,
+            ranking_uuid,
+            player_uuid
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        let snapshot = sqlx::query!(
+            "SELECT rating_volatility, games_count FROM ranking_snapshot_cache
+            WHERE ranking_uuid = ? AND player_uuid = ?",
+            ranking_uuid,
+            player_uuid
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let rating = match last_result {
+            Some(row) => Rating {
+                value: row.rating_after,
+                deviation: row.rating_deviation_after,
+                volatility: snapshot.as_ref().and_then(|snapshot| snapshot.rating_volatility),
+            },
+            None => algorithm.initial(),
+        };
+
+        ratings.push((rating, snapshot.map_or(0, |snapshot| snapshot.games_count)));
+    }
+
+    Ok(ratings)
+}
+
+/// Replays every visible game session of the ranking,
+/// games are returned in order they ended
+async fn load_computed_games(
+    conn: &mut sqlx::SqliteConnection,
+    ranking_uuid: &str,
+) -> Result<Vec<ComputedGame>, anyhow::Error> {
+    let sessions = sqlx::query!(
+        r#"SELECT
+            uuid, place_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid,
+            is_unranked as "is_unranked: bool", is_novice_friendly as "is_novice_friendly: bool"
+        FROM game_sessions
+        WHERE ranking_uuid = ?
+        AND is_hidden = 0
+        AND is_compute_skipped = 0
+        ORDER BY created_at ASC, rowid ASC"#,
+        ranking_uuid
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let rows = sqlx::query!(
+        "SELECT e.game_session_uuid, e.event_type, e.event_data, e.created_at
+        FROM game_session_events e
+        INNER JOIN game_sessions gs ON gs.uuid = e.game_session_uuid
+        WHERE gs.ranking_uuid = ?
+        ORDER BY e.created_at ASC, e.rowid ASC",
+        ranking_uuid
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut events: HashMap<String, Vec<GameEvent>> = HashMap::new();
+    for row in rows {
+        events.entry(row.game_session_uuid).or_default().push(GameEvent {
+            event_type: row.event_type,
+            event_data: row.event_data,
+            created_at: row.created_at,
+        });
+    }
+
+    let mut games = sessions
+        .into_iter()
+        .filter_map(|session| {
+            let players = [
+                session.player1_uuid,
+                session.player2_uuid,
+                session.player3_uuid,
+                session.player4_uuid,
+            ];
+            let session_events = events.get(&session.uuid).map_or(&[][..], |events| events.as_slice());
+
+            match scoring::replay(&players, session_events) {
+                Ok(Some(outcome)) => Some(ComputedGame {
+                    game_session_uuid: session.uuid,
+                    place_uuid: session.place_uuid,
+                    players,
+                    is_unranked: session.is_unranked,
+                    is_novice_friendly: session.is_novice_friendly,
+                    outcome,
+                }),
+                Ok(None) => None,
+                Err(err) => {
+                    warn!("skipping game session [{}], unable to replay events: {}", session.uuid, err);
+
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    games.sort_by_key(|game| game.outcome.ended_at);
+
+    debug!("replayed {} games of ranking [{}]", games.len(), ranking_uuid);
+
+    Ok(games)
+}
@@ -7,7 +7,27 @@ pub struct Config {
     pub database_conn_timeout: u64,
     pub bind_interface: String,
     pub firebase_project_id: String,
-    pub database_pragma_cache_size: u32
+    pub database_pragma_cache_size: u32,
+    #[serde(default = "default_ranking_compute_interval")]
+    pub ranking_compute_interval: u64,
+    // path to federation export, json file or directory with csv files
+    pub sync_source: Option<String>,
+    #[serde(default = "default_sync_interval")]
+    pub sync_interval: u64,
+    #[serde(default = "default_place_rank_interval")]
+    pub place_rank_interval: u64,
+}
+
+fn default_ranking_compute_interval() -> u64 {
+    300
+}
+
+fn default_sync_interval() -> u64 {
+    3600
+}
+
+fn default_place_rank_interval() -> u64 {
+    86400
 }
 
 pub fn init_config() -> Config {
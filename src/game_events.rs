@@ -1,4 +1,4 @@
-use axum::{extract::Path, handler::Handler, response::IntoResponse, routing::{post, get}, Json, Router};
+use axum::{extract::Path, handler::Handler, Extension, response::IntoResponse, routing::{post, get}, Json, Router};
 use core::ops::Deref;
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
@@ -8,6 +8,7 @@ use validator::{Validate, ValidationError};
 
 use crate::{
     app::AppError,
+    compute::SharedComputeService,
     db::DatabaseConnection,
     firebase,
     games::GameSessionUuid,
@@ -128,6 +129,7 @@ pub async fn events_end(
     _claims: firebase::FirebaseClaims,
     current_user: users::CurrentUser,
     game_session: GameSessionUuid,
+    Extension(compute): Extension<SharedComputeService>,
     db: DatabaseConnection,
 ) -> Result<impl IntoResponse, AppError> {
     let GameSessionUuid(game_session_uuid) = &game_session;
@@ -159,6 +161,8 @@ pub async fn events_end(
         .execute(&mut conn)
         .await?;
 
+    compute.mark_dirty(&mut conn, game_session_uuid).await?;
+
     Ok(StatusCode::CREATED)
 }
 
@@ -166,6 +170,7 @@ pub async fn events_undo_game(
     _claims: firebase::FirebaseClaims,
     current_user: users::CurrentUser,
     game_session: GameSessionUuid,
+    Extension(compute): Extension<SharedComputeService>,
     db: DatabaseConnection,
 ) -> Result<impl IntoResponse, AppError> {
     let GameSessionUuid(game_session_uuid) = &game_session;
@@ -197,6 +202,8 @@ pub async fn events_undo_game(
         .execute(&mut conn)
         .await?;
 
+    compute.mark_dirty(&mut conn, game_session_uuid).await?;
+
     Ok(StatusCode::CREATED)
 }
 
@@ -204,6 +211,7 @@ pub async fn events_undo_last(
     _claims: firebase::FirebaseClaims,
     current_user: users::CurrentUser,
     GameSessionUuid(game_session_uuid): GameSessionUuid,
+    Extension(compute): Extension<SharedComputeService>,
     DatabaseConnection(conn): DatabaseConnection,
 ) -> Result<impl IntoResponse, AppError> {
     let mut conn = conn;
@@ -225,6 +233,8 @@ pub async fn events_undo_last(
         .execute(&mut conn)
         .await?;
 
+    compute.mark_dirty(&mut conn, &game_session_uuid).await?;
+
     Ok(StatusCode::CREATED)
 }
 
@@ -234,22 +244,21 @@ function = "validate_event_finish_round_tsumo_scorers_input",
 skip_on_field_errors = false
 ))]
 pub struct GameEventsFinishRoundTsumoDelta {
-    scoring_player_uuid: String,
+    pub scoring_player_uuid: String,
     #[allow(dead_code)]
     tile_set: Option<String>,
-    // unused for now
-    han: Option<i64>,
-    fu: Option<i64>,
-    yakuman: Option<i64>,
+    pub han: Option<i64>,
+    pub fu: Option<i64>,
+    pub yakuman: Option<i64>,
 }
 
 #[derive(Deserialize, Serialize, Validate)]
 pub struct GameEventsFinishRoundByTsumo {
     #[validate]
     #[validate(length(equal = 1))]
-    delta: Vec<GameEventsFinishRoundTsumoDelta>,
+    pub delta: Vec<GameEventsFinishRoundTsumoDelta>,
     #[validate(length(min = 0, max = 4))]
-    declared_riichi_player_uuids: Vec<String>,
+    pub declared_riichi_player_uuids: Vec<String>,
 }
 
 pub async fn events_finish_round_by_tsumo(
@@ -289,23 +298,22 @@ function = "validate_event_finish_round_ron_scorers_input",
 skip_on_field_errors = false
 ))]
 pub struct GameEventsFinishRoundRonDelta {
-    scoring_player_uuid: String,
-    losing_player_uuid: String,
+    pub scoring_player_uuid: String,
+    pub losing_player_uuid: String,
     #[allow(dead_code)]
     tile_set: Option<String>,
-    // unused for now
-    han: Option<i64>,
-    fu: Option<i64>,
-    yakuman: Option<i64>,
+    pub han: Option<i64>,
+    pub fu: Option<i64>,
+    pub yakuman: Option<i64>,
 }
 
 #[derive(Deserialize, Serialize, Validate)]
 pub struct GameEventsFinishRoundByRon {
     #[validate]
     #[validate(length(min = 1, max = 3))]
-    delta: Vec<GameEventsFinishRoundRonDelta>,
+    pub delta: Vec<GameEventsFinishRoundRonDelta>,
     #[validate(length(min = 0, max = 4))]
-    declared_riichi_player_uuids: Vec<String>,
+    pub declared_riichi_player_uuids: Vec<String>,
 }
 
 pub async fn events_finish_round_by_ron(
@@ -342,9 +350,9 @@ pub async fn events_finish_round_by_ron(
 #[derive(Deserialize, Serialize, Validate)]
 pub struct GameEventsFinishRoundByRyuukyoku {
     #[validate(length(min = 0, max = 4))]
-    tenpai_player_uuids: Vec<String>,
+    pub tenpai_player_uuids: Vec<String>,
     #[validate(length(min = 0, max = 4))]
-    declared_riichi_player_uuids: Vec<String>,
+    pub declared_riichi_player_uuids: Vec<String>,
 }
 
 pub async fn events_finish_round_by_ryuukyoku(
@@ -380,7 +388,7 @@ pub async fn events_finish_round_by_ryuukyoku(
 
 #[derive(Deserialize, Serialize, Validate)]
 pub struct GameEventsFinishRoundByChonbo {
-    player_uuid: String,
+    pub player_uuid: String,
 }
 
 pub async fn events_finish_round_by_chonbo(
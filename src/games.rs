@@ -12,12 +12,13 @@ use rand::prelude::SliceRandom;
 use serde::Deserialize;
 use serde_json::json;
 use tower_http::compression::CompressionLayer;
-use validator::Validate;
+use sqlx::Connection;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 use crate::{
     app::AppError,
     db::DatabaseConnection,
-    firebase, users,
+    firebase, geo, leagues, search, users,
     validate::{ValidatedJson, ValidatedQuery},
 };
 
@@ -132,36 +133,135 @@ where
     }
 }
 
+/// Seated player, either uuid of an existing player or a walk-in guest
+/// which is created in the ranking together with the game session
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum GameSessionsCreatePlayer {
+    Uuid(String),
+    Guest(GameSessionsCreateGuest),
+}
+
+#[derive(Deserialize)]
+pub struct GameSessionsCreateGuest {
+    nickname: String,
+    country_code: Option<String>,
+}
+
 #[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_game_sessions_create_input"))]
 pub struct GameSessionsCreate {
-    ranking_uuid: String,
-    #[validate(length(equal = 4))]
-    players_uuids: Vec<String>,
+    players_uuids: Vec<GameSessionsCreatePlayer>,
     place_uuid: String,
     is_shuffled: bool,
     is_novice_friendly: bool,
     is_unranked: bool,
+    // league game of the fixture, seated players must represent its entrants
+    #[validate(length(equal = 36))]
+    league_fixture_uuid: Option<String>,
+}
+
+fn validate_game_sessions_create_input(input: &GameSessionsCreate) -> Result<(), ValidationError> {
+    let is_valid = input.players_uuids.len() == 4 && input.players_uuids.iter().all(|player| match player {
+        GameSessionsCreatePlayer::Uuid(uuid) => uuid.len() == crate::app::UUID_STRLEN,
+        GameSessionsCreatePlayer::Guest(guest) => {
+            (1..=32).contains(&guest.nickname.trim().chars().count())
+                && guest.country_code.as_deref().is_none_or(|code| geo::normalize_country(code).is_some())
+        }
+    });
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid player or guest"))
+    }
 }
 
 pub async fn game_sessions_create(
     _claims: firebase::FirebaseClaims,
     current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
     ValidatedJson(input): ValidatedJson<GameSessionsCreate>,
     DatabaseConnection(conn): DatabaseConnection,
 ) -> Result<impl IntoResponse, AppError> {
     let mut conn = conn;
     let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
 
-    let mut input = input;
+    sqlx::query_scalar!(
+        "SELECT 1 FROM rankings_cache WHERE uuid = ? AND deleted_at IS NULL",
+        ranking_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::RankingNotFound)?;
+
+    // guest without country is from the same country as player creating the session
+    let creator_country_code = sqlx::query_scalar!(
+        "SELECT country_code FROM players_cache WHERE uuid = ? AND deleted_at IS NULL",
+        current_user.player_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?;
+
+    let mut tx = conn.begin().await?;
+    let mut players_uuids = Vec::with_capacity(input.players_uuids.len());
+
+    for player in &input.players_uuids {
+        let player_uuid = match player {
+            GameSessionsCreatePlayer::Uuid(player_uuid) => player_uuid.clone(),
+            GameSessionsCreatePlayer::Guest(guest) => {
+                let player_uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+                let nickname = guest.nickname.trim();
+                let search_terms = search::player_search_terms("", Some(nickname), None, None, false);
+                let country_code = guest
+                    .country_code
+                    .as_deref()
+                    .and_then(geo::normalize_country)
+                    .or(creator_country_code.as_deref());
+
+                let country_code = match country_code {
+                    Some(country_code) => country_code,
+                    None => {
+                        let mut errors = ValidationErrors::new();
+                        errors.add("country_code", ValidationError::new("guest country is unknown"));
+
+                        return Err(AppError::ValidationError(errors));
+                    }
+                };
+
+                sqlx::query!(
+                    "INSERT INTO players_cache (
+                        uuid, ranking_uuid, usma_id, first_name, last_name, city, region, country_code,
+                        nickname, is_exam_done, is_gdpr_agreed, is_guest, is_static, search_terms, created_at
+                    )
+                    VALUES (?, ?, '', NULL, NULL, NULL, NULL, ?, ?, 0, 0, 1, 0, ?, strftime('%s', 'now'))",
+                    player_uuid,
+                    ranking_uuid,
+                    country_code,
+                    nickname,
+                    search_terms,
+                )
+                .execute(&mut tx)
+                .await?;
+
+                player_uuid
+            }
+        };
+
+        players_uuids.push(player_uuid);
+    }
 
     if input.is_shuffled {
-        input
-            .players_uuids
+        players_uuids
             .as_mut_slice()
             .shuffle(&mut rand::thread_rng());
     }
 
-    let input = input;
+    if let Some(league_fixture_uuid) = &input.league_fixture_uuid {
+        leagues::check_fixture_players(&mut tx, &ranking_uuid, league_fixture_uuid, &players_uuids).await?;
+    }
+
+    let is_league_game = input.league_fixture_uuid.is_some();
 
     sqlx::query!(
         // sql query inserting into game sessions table
@@ -171,33 +271,38 @@ pub async fn game_sessions_create(
             tournament_uuid, place_uuid, is_shuffled, is_novice_friendly, is_unranked,
             is_announced, is_player_certified_referee, is_league_game, ranking_uuid,
             is_tonpuu, is_too_slow, is_tenant_host, is_hidden, is_not_computed,
-            is_verification_required, is_compute_skipped, created_at
+            is_verification_required, is_compute_skipped, league_fixture_uuid, created_at
         )
         VALUES (
             ?, ?, ?, ?, ?, ?,
             NULL, ?, ?, ?, ?,
-            0, 0, 0, ?,
+            0, 0, ?, ?,
             0, 0, 0, 0, 1,
-            0, 0, strftime('%s', 'now')
+            0, 0, ?, strftime('%s', 'now')
         )
         ",
         uuid,
         current_user.player_uuid,
-        input.players_uuids[0],
-        input.players_uuids[1],
-        input.players_uuids[2],
-        input.players_uuids[3],
+        players_uuids[0],
+        players_uuids[1],
+        players_uuids[2],
+        players_uuids[3],
         input.place_uuid,
         input.is_shuffled,
         input.is_novice_friendly,
         input.is_unranked,
-        input.ranking_uuid
+        is_league_game,
+        ranking_uuid,
+        input.league_fixture_uuid,
     )
-    .execute(&mut conn)
+    .execute(&mut tx)
     .await?;
 
+    tx.commit().await?;
+
     Ok(Json(json!({
         "uuid": uuid,
+        "players_uuids": players_uuids,
     })))
 }
 
@@ -260,7 +365,8 @@ pub async fn game_sessions_show(
     let game_session = sqlx::query!(
         "SELECT
             uuid, creator_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid,
-            place_uuid, is_shuffled, is_novice_friendly, is_unranked, created_at
+            place_uuid, is_shuffled, is_novice_friendly, is_unranked, is_league_game,
+            league_fixture_uuid, created_at
         FROM game_sessions
         WHERE uuid = ?
         LIMIT 1",
@@ -280,6 +386,18 @@ pub async fn game_sessions_show(
         .fetch_all(&mut conn)
         .await?;
 
+    let results = sqlx::query!(
+        "SELECT
+            player_uuid, seat, placement, points, rating_before, rating_after,
+            rating_deviation_after, rank_points, weight, weighting
+        FROM game_session_results_cache
+        WHERE game_session_uuid = ?
+        ORDER BY seat ASC",
+        game_session_uuid,
+    )
+        .fetch_all(&mut conn)
+        .await?;
+
     Ok(Json(json!({
         "items": vec![
             json!({
@@ -290,6 +408,8 @@ pub async fn game_sessions_show(
                 "is_shuffled": game_session.is_shuffled,
                 "is_novice_friendly": game_session.is_novice_friendly,
                 "is_unranked": game_session.is_unranked,
+                "is_league_game": game_session.is_league_game,
+                "league_fixture_uuid": game_session.league_fixture_uuid,
                 "created_at": game_session.created_at,
                 "$events": json!({
                     "items": events.iter().map(|row| {
@@ -302,6 +422,23 @@ pub async fn game_sessions_show(
                         })
                     }).collect::<Vec<_>>(),
                     "count": events.len()
+                }),
+                "$results": json!({
+                    "items": results.iter().map(|row| {
+                        json!({
+                            "player_uuid": row.player_uuid,
+                            "seat": row.seat,
+                            "placement": row.placement,
+                            "points": row.points,
+                            "rating_before": row.rating_before,
+                            "rating_after": row.rating_after,
+                            "rating_deviation_after": row.rating_deviation_after,
+                            "rank_points": row.rank_points,
+                            "weight": row.weight,
+                            "weighting": row.weighting,
+                        })
+                    }).collect::<Vec<_>>(),
+                    "count": results.len()
                 })
             })
         ],
@@ -0,0 +1,211 @@
+use axum::{response::IntoResponse, routing::{get, post}, Json, Router};
+use serde_json::json;
+use sqlx::Connection;
+
+use crate::{
+    app::AppError,
+    db::DatabaseConnection,
+    firebase, search, users,
+};
+
+/// Personal fields (first_name, last_name, city) of the player are visible only
+/// when player agreed to processing them, or to the player themselves
+pub fn is_visible(is_gdpr_agreed: bool, player_uuid: &str, current_user: &users::CurrentUser) -> bool {
+    is_gdpr_agreed || player_uuid == current_user.player_uuid
+}
+
+pub fn mask<T: Clone>(visible: bool, value: &Option<T>) -> Option<T> {
+    if visible {
+        value.clone()
+    } else {
+        None
+    }
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/users/@me/export",
+            get(users_me_export),
+        )
+        .route(
+            "/users/@me/erasure",
+            post(users_me_erasure),
+        )
+}
+
+/// Every piece of data stored about the player of the current user
+pub async fn users_me_export(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let player_uuid = current_user.player_uuid.as_str();
+
+    let player = sqlx::query!(
+        r#"SELECT
+            uuid, ranking_uuid, usma_id, ema_id, first_name, last_name, city, region, country_code,
+            nickname, is_exam_done as "is_exam_done: bool",
+            is_gdpr_agreed as "is_gdpr_agreed: bool",
+            is_guest as "is_guest: bool", is_static as "is_static: bool", created_at
+        FROM players_cache WHERE uuid = ?"#,
+        player_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::PlayerNotFound)?;
+
+    let sessions = sqlx::query!(
+        r#"SELECT
+            uuid, ranking_uuid, creator_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid,
+            place_uuid, tournament_uuid, is_unranked as "is_unranked: bool",
+            is_hidden as "is_hidden: bool", created_at
+        FROM game_sessions
+        WHERE ?1 IN (creator_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid)
+        ORDER BY created_at ASC"#,
+        player_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let events = sqlx::query!(
+        "SELECT e.uuid, e.game_session_uuid, e.creator_uuid, e.event_type, e.event_data, e.created_at
+        FROM game_session_events e
+        INNER JOIN game_sessions s ON s.uuid = e.game_session_uuid
+        WHERE ?1 IN (s.creator_uuid, s.player1_uuid, s.player2_uuid, s.player3_uuid, s.player4_uuid)
+        ORDER BY e.created_at ASC, e.rowid ASC",
+        player_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let results = sqlx::query!(
+        "SELECT game_session_uuid, seat, placement, points, rating_before, rating_after, rank_points, ended_at
+        FROM game_session_results_cache WHERE player_uuid = ? ORDER BY ended_at ASC",
+        player_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "user_uid": current_user.user_uid,
+                "player_uuid": player.uuid,
+                "$player": json!({
+                    "uuid": player.uuid,
+                    "ranking_uuid": player.ranking_uuid,
+                    "usma_id": player.usma_id,
+                    "ema_id": player.ema_id,
+                    "first_name": player.first_name,
+                    "last_name": player.last_name,
+                    "city": player.city,
+                    "region": player.region,
+                    "country_code": player.country_code,
+                    "nickname": player.nickname,
+                    "is_exam_done": player.is_exam_done,
+                    "is_gdpr_agreed": player.is_gdpr_agreed,
+                    "is_guest": player.is_guest,
+                    "is_static": player.is_static,
+                    "created_at": player.created_at,
+                }),
+                "$game_sessions": json!({
+                    "items": sessions.iter().map(|row| json!({
+                        "uuid": row.uuid,
+                        "ranking_uuid": row.ranking_uuid,
+                        "creator_uuid": row.creator_uuid,
+                        "players_uuids": [row.player1_uuid, row.player2_uuid, row.player3_uuid, row.player4_uuid],
+                        "place_uuid": row.place_uuid,
+                        "tournament_uuid": row.tournament_uuid,
+                        "is_unranked": row.is_unranked,
+                        "is_hidden": row.is_hidden,
+                        "created_at": row.created_at,
+                    })).collect::<Vec<_>>(),
+                    "count": sessions.len(),
+                }),
+                "$game_session_events": json!({
+                    "items": events.iter().map(|row| json!({
+                        "uuid": row.uuid,
+                        "game_session_uuid": row.game_session_uuid,
+                        "creator_uuid": row.creator_uuid,
+                        "event_type": row.event_type,
+                        "event_data": row.event_data.as_deref().and_then(|data| serde_json::from_str::<serde_json::Value>(data).ok()),
+                        "created_at": row.created_at,
+                    })).collect::<Vec<_>>(),
+                    "count": events.len(),
+                }),
+                "$results": json!({
+                    "items": results.iter().map(|row| json!({
+                        "game_session_uuid": row.game_session_uuid,
+                        "seat": row.seat,
+                        "placement": row.placement,
+                        "points": row.points,
+                        "rating_before": row.rating_before,
+                        "rating_after": row.rating_after,
+                        "rank_points": row.rank_points,
+                        "ended_at": row.ended_at,
+                    })).collect::<Vec<_>>(),
+                    "count": results.len(),
+                }),
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+/// Pseudonymizes the player of the current user, unlinks the user from the player and invalidates
+/// unused claim codes. player uuid stays so game sessions and results of other players are kept intact
+pub async fn users_me_erasure(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let player_uuid = current_user.player_uuid.as_str();
+    let nickname = format!("anonymous-{}", &player_uuid[..8.min(player_uuid.len())]);
+    let search_terms = search::player_search_terms("", Some(&nickname), None, None, false);
+
+    let mut tx = conn.begin().await?;
+
+    let erased = sqlx::query!(
+        "UPDATE players_cache SET
+            usma_id = '', ema_id = NULL, first_name = NULL, last_name = NULL, city = NULL,
+            nickname = ?, is_gdpr_agreed = 0, search_terms = ?, erased_at = strftime('%s', 'now')
+        WHERE uuid = ?",
+        nickname,
+        search_terms,
+        player_uuid,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    if erased.rows_affected() == 0 {
+        return Err(AppError::PlayerNotFound);
+    }
+
+    sqlx::query!("DELETE FROM user_player WHERE user_uid = ?", current_user.user_uid)
+        .execute(&mut tx)
+        .await?;
+
+    // erased player can't be claimed again
+    sqlx::query!(
+        "UPDATE player_claim_codes SET expires_at = strftime('%s', 'now')
+        WHERE player_uuid = ? AND redeemed_at IS NULL AND expires_at > strftime('%s', 'now')",
+        player_uuid,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "player_uuid": player_uuid,
+                "nickname": nickname,
+            })
+        ],
+        "count": 1,
+    })))
+}
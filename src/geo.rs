@@ -0,0 +1,537 @@
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+use validator::ValidationError;
+
+use crate::search::fold;
+
+/// ISO 3166-1 alpha-2 codes with english short names
+pub const COUNTRIES: &[(&str, &str)] = &[
+    ("AD", "Andorra"),
+    ("AE", "United Arab Emirates"),
+    ("AF", "Afghanistan"),
+    ("AG", "Antigua and Barbuda"),
+    ("AI", "Anguilla"),
+    ("AL", "Albania"),
+    ("AM", "Armenia"),
+    ("AO", "Angola"),
+    ("AQ", "Antarctica"),
+    ("AR", "Argentina"),
+    ("AS", "American Samoa"),
+    ("AT", "Austria"),
+    ("AU", "Australia"),
+    ("AW", "Aruba"),
+    ("AX", "Åland Islands"),
+    ("AZ", "Azerbaijan"),
+    ("BA", "Bosnia and Herzegovina"),
+    ("BB", "Barbados"),
+    ("BD", "Bangladesh"),
+    ("BE", "Belgium"),
+    ("BF", "Burkina Faso"),
+    ("BG", "Bulgaria"),
+    ("BH", "Bahrain"),
+    ("BI", "Burundi"),
+    ("BJ", "Benin"),
+    ("BL", "Saint Barthélemy"),
+    ("BM", "Bermuda"),
+    ("BN", "Brunei Darussalam"),
+    ("BO", "Bolivia"),
+    ("BQ", "Bonaire, Sint Eustatius and Saba"),
+    ("BR", "Brazil"),
+    ("BS", "Bahamas"),
+    ("BT", "Bhutan"),
+    ("BV", "Bouvet Island"),
+    ("BW", "Botswana"),
+    ("BY", "Belarus"),
+    ("BZ", "Belize"),
+    ("CA", "Canada"),
+    ("CC", "Cocos (Keeling) Islands"),
+    ("CD", "Congo, Democratic Republic of the"),
+    ("CF", "Central African Republic"),
+    ("CG", "Congo"),
+    ("CH", "Switzerland"),
+    ("CI", "Côte d'Ivoire"),
+    ("CK", "Cook Islands"),
+    ("CL", "Chile"),
+    ("CM", "Cameroon"),
+    ("CN", "China"),
+    ("CO", "Colombia"),
+    ("CR", "Costa Rica"),
+    ("CU", "Cuba"),
+    ("CV", "Cabo Verde"),
+    ("CW", "Curaçao"),
+    ("CX", "Christmas Island"),
+    ("CY", "Cyprus"),
+    ("CZ", "Czechia"),
+    ("DE", "Germany"),
+    ("DJ", "Djibouti"),
+    ("DK", "Denmark"),
+    ("DM", "Dominica"),
+    ("DO", "Dominican Republic"),
+    ("DZ", "Algeria"),
+    ("EC", "Ecuador"),
+    ("EE", "Estonia"),
+    ("EG", "Egypt"),
+    ("EH", "Western Sahara"),
+    ("ER", "Eritrea"),
+    ("ES", "Spain"),
+    ("ET", "Ethiopia"),
+    ("FI", "Finland"),
+    ("FJ", "Fiji"),
+    ("FK", "Falkland Islands"),
+    ("FM", "Micronesia"),
+    ("FO", "Faroe Islands"),
+    ("FR", "France"),
+    ("GA", "Gabon"),
+    ("GB", "United Kingdom"),
+    ("GD", "Grenada"),
+    ("GE", "Georgia"),
+    ("GF", "French Guiana"),
+    ("GG", "Guernsey"),
+    ("GH", "Ghana"),
+    ("GI", "Gibraltar"),
+    ("GL", "Greenland"),
+    ("GM", "Gambia"),
+    ("GN", "Guinea"),
+    ("GP", "Guadeloupe"),
+    ("GQ", "Equatorial Guinea"),
+    ("GR", "Greece"),
+    ("GS", "South Georgia and the South Sandwich Islands"),
+    ("GT", "Guatemala"),
+    ("GU", "Guam"),
+    ("GW", "Guinea-Bissau"),
+    ("GY", "Guyana"),
+    ("HK", "Hong Kong"),
+    ("HM", "Heard Island and McDonald Islands"),
+    ("HN", "Honduras"),
+    ("HR", "Croatia"),
+    ("HT", "Haiti"),
+    ("HU", "Hungary"),
+    ("ID", "Indonesia"),
+    ("IE", "Ireland"),
+    ("IL", "Israel"),
+    ("IM", "Isle of Man"),
+    ("IN", "India"),
+    ("IO", "British Indian Ocean Territory"),
+    ("IQ", "Iraq"),
+    ("IR", "Iran"),
+    ("IS", "Iceland"),
+    ("IT", "Italy"),
+    ("JE", "Jersey"),
+    ("JM", "Jamaica"),
+    ("JO", "Jordan"),
+    ("JP", "Japan"),
+    ("KE", "Kenya"),
+    ("KG", "Kyrgyzstan"),
+    ("KH", "Cambodia"),
+    ("KI", "Kiribati"),
+    ("KM", "Comoros"),
+    ("KN", "Saint Kitts and Nevis"),
+    ("KP", "Korea, Democratic People's Republic of"),
+    ("KR", "Korea, Republic of"),
+    ("KW", "Kuwait"),
+    ("KY", "Cayman Islands"),
+    ("KZ", "Kazakhstan"),
+    ("LA", "Lao People's Democratic Republic"),
+    ("LB", "Lebanon"),
+    ("LC", "Saint Lucia"),
+    ("LI", "Liechtenstein"),
+    ("LK", "Sri Lanka"),
+    ("LR", "Liberia"),
+    ("LS", "Lesotho"),
+    ("LT", "Lithuania"),
+    ("LU", "Luxembourg"),
+    ("LV", "Latvia"),
+    ("LY", "Libya"),
+    ("MA", "Morocco"),
+    ("MC", "Monaco"),
+    ("MD", "Moldova"),
+    ("ME", "Montenegro"),
+    ("MF", "Saint Martin (French part)"),
+    ("MG", "Madagascar"),
+    ("MH", "Marshall Islands"),
+    ("MK", "North Macedonia"),
+    ("ML", "Mali"),
+    ("MM", "Myanmar"),
+    ("MN", "Mongolia"),
+    ("MO", "Macao"),
+    ("MP", "Northern Mariana Islands"),
+    ("MQ", "Martinique"),
+    ("MR", "Mauritania"),
+    ("MS", "Montserrat"),
+    ("MT", "Malta"),
+    ("MU", "Mauritius"),
+    ("MV", "Maldives"),
+    ("MW", "Malawi"),
+    ("MX", "Mexico"),
+    ("MY", "Malaysia"),
+    ("MZ", "Mozambique"),
+    ("NA", "Namibia"),
+    ("NC", "New Caledonia"),
+    ("NE", "Niger"),
+    ("NF", "Norfolk Island"),
+    ("NG", "Nigeria"),
+    ("NI", "Nicaragua"),
+    ("NL", "Netherlands"),
+    ("NO", "Norway"),
+    ("NP", "Nepal"),
+    ("NR", "Nauru"),
+    ("NU", "Niue"),
+    ("NZ", "New Zealand"),
+    ("OM", "Oman"),
+    ("PA", "Panama"),
+    ("PE", "Peru"),
+    ("PF", "French Polynesia"),
+    ("PG", "Papua New Guinea"),
+    ("PH", "Philippines"),
+    ("PK", "Pakistan"),
+    ("PL", "Poland"),
+    ("PM", "Saint Pierre and Miquelon"),
+    ("PN", "Pitcairn"),
+    ("PR", "Puerto Rico"),
+    ("PS", "Palestine"),
+    ("PT", "Portugal"),
+    ("PW", "Palau"),
+    ("PY", "Paraguay"),
+    ("QA", "Qatar"),
+    ("RE", "Réunion"),
+    ("RO", "Romania"),
+    ("RS", "Serbia"),
+    ("RU", "Russian Federation"),
+    ("RW", "Rwanda"),
+    ("SA", "Saudi Arabia"),
+    ("SB", "Solomon Islands"),
+    ("SC", "Seychelles"),
+    ("SD", "Sudan"),
+    ("SE", "Sweden"),
+    ("SG", "Singapore"),
+    ("SH", "Saint Helena, Ascension and Tristan da Cunha"),
+    ("SI", "Slovenia"),
+    ("SJ", "Svalbard and Jan Mayen"),
+    ("SK", "Slovakia"),
+    ("SL", "Sierra Leone"),
+    ("SM", "San Marino"),
+    ("SN", "Senegal"),
+    ("SO", "Somalia"),
+    ("SR", "Suriname"),
+    ("SS", "South Sudan"),
+    ("ST", "Sao Tome and Principe"),
+    ("SV", "El Salvador"),
+    ("SX", "Sint Maarten (Dutch part)"),
+    ("SY", "Syrian Arab Republic"),
+    ("SZ", "Eswatini"),
+    ("TC", "Turks and Caicos Islands"),
+    ("TD", "Chad"),
+    ("TF", "French Southern Territories"),
+    ("TG", "Togo"),
+    ("TH", "Thailand"),
+    ("TJ", "Tajikistan"),
+    ("TK", "Tokelau"),
+    ("TL", "Timor-Leste"),
+    ("TM", "Turkmenistan"),
+    ("TN", "Tunisia"),
+    ("TO", "Tonga"),
+    ("TR", "Türkiye"),
+    ("TT", "Trinidad and Tobago"),
+    ("TV", "Tuvalu"),
+    ("TW", "Taiwan"),
+    ("TZ", "Tanzania"),
+    ("UA", "Ukraine"),
+    ("UG", "Uganda"),
+    ("UM", "United States Minor Outlying Islands"),
+    ("US", "United States of America"),
+    ("UY", "Uruguay"),
+    ("UZ", "Uzbekistan"),
+    ("VA", "Holy See"),
+    ("VC", "Saint Vincent and the Grenadines"),
+    ("VE", "Venezuela"),
+    ("VG", "Virgin Islands (British)"),
+    ("VI", "Virgin Islands (U.S.)"),
+    ("VN", "Viet Nam"),
+    ("VU", "Vanuatu"),
+    ("WF", "Wallis and Futuna"),
+    ("WS", "Samoa"),
+    ("YE", "Yemen"),
+    ("YT", "Mayotte"),
+    ("ZA", "South Africa"),
+    ("ZM", "Zambia"),
+    ("ZW", "Zimbabwe"),
+];
+
+/// ISO 3166-2 subdivisions of countries federation players come from, with local name
+/// and english name when it differs. subdivisions of other countries are validated by format only
+pub const SUBDIVISIONS: &[(&str, &str, Option<&str>)] = &[
+    ("PL-02", "Dolnośląskie", Some("Lower Silesia")),
+    ("PL-04", "Kujawsko-pomorskie", Some("Kuyavia-Pomerania")),
+    ("PL-06", "Lubelskie", Some("Lublin")),
+    ("PL-08", "Lubuskie", Some("Lubusz")),
+    ("PL-10", "Łódzkie", Some("Łódź")),
+    ("PL-12", "Małopolskie", Some("Lesser Poland")),
+    ("PL-14", "Mazowieckie", Some("Masovia")),
+    ("PL-16", "Opolskie", Some("Opole")),
+    ("PL-18", "Podkarpackie", Some("Subcarpathia")),
+    ("PL-20", "Podlaskie", Some("Podlachia")),
+    ("PL-22", "Pomorskie", Some("Pomerania")),
+    ("PL-24", "Śląskie", Some("Silesia")),
+    ("PL-26", "Świętokrzyskie", Some("Holy Cross")),
+    ("PL-28", "Warmińsko-mazurskie", Some("Warmia-Masuria")),
+    ("PL-30", "Wielkopolskie", Some("Greater Poland")),
+    ("PL-32", "Zachodniopomorskie", Some("West Pomerania")),
+    ("DE-BW", "Baden-Württemberg", None),
+    ("DE-BY", "Bayern", Some("Bavaria")),
+    ("DE-BE", "Berlin", None),
+    ("DE-BB", "Brandenburg", None),
+    ("DE-HB", "Bremen", None),
+    ("DE-HH", "Hamburg", None),
+    ("DE-HE", "Hessen", Some("Hesse")),
+    ("DE-MV", "Mecklenburg-Vorpommern", Some("Mecklenburg-Western Pomerania")),
+    ("DE-NI", "Niedersachsen", Some("Lower Saxony")),
+    ("DE-NW", "Nordrhein-Westfalen", Some("North Rhine-Westphalia")),
+    ("DE-RP", "Rheinland-Pfalz", Some("Rhineland-Palatinate")),
+    ("DE-SL", "Saarland", None),
+    ("DE-SN", "Sachsen", Some("Saxony")),
+    ("DE-ST", "Sachsen-Anhalt", Some("Saxony-Anhalt")),
+    ("DE-SH", "Schleswig-Holstein", None),
+    ("DE-TH", "Thüringen", Some("Thuringia")),
+    ("CZ-10", "Praha", Some("Prague")),
+    ("CZ-20", "Středočeský kraj", Some("Central Bohemia")),
+    ("CZ-31", "Jihočeský kraj", Some("South Bohemia")),
+    ("CZ-32", "Plzeňský kraj", Some("Plzeň")),
+    ("CZ-41", "Karlovarský kraj", Some("Karlovy Vary")),
+    ("CZ-42", "Ústecký kraj", Some("Ústí nad Labem")),
+    ("CZ-51", "Liberecký kraj", Some("Liberec")),
+    ("CZ-52", "Královéhradecký kraj", Some("Hradec Králové")),
+    ("CZ-53", "Pardubický kraj", Some("Pardubice")),
+    ("CZ-63", "Kraj Vysočina", Some("Vysočina")),
+    ("CZ-64", "Jihomoravský kraj", Some("South Moravia")),
+    ("CZ-71", "Olomoucký kraj", Some("Olomouc")),
+    ("CZ-72", "Zlínský kraj", Some("Zlín")),
+    ("CZ-80", "Moravskoslezský kraj", Some("Moravian-Silesian")),
+    ("AT-1", "Burgenland", None),
+    ("AT-2", "Kärnten", Some("Carinthia")),
+    ("AT-3", "Niederösterreich", Some("Lower Austria")),
+    ("AT-4", "Oberösterreich", Some("Upper Austria")),
+    ("AT-5", "Salzburg", None),
+    ("AT-6", "Steiermark", Some("Styria")),
+    ("AT-7", "Tirol", Some("Tyrol")),
+    ("AT-8", "Vorarlberg", None),
+    ("AT-9", "Wien", Some("Vienna")),
+    ("NL-DR", "Drenthe", None),
+    ("NL-FL", "Flevoland", None),
+    ("NL-FR", "Fryslân", Some("Friesland")),
+    ("NL-GE", "Gelderland", None),
+    ("NL-GR", "Groningen", None),
+    ("NL-LI", "Limburg", None),
+    ("NL-NB", "Noord-Brabant", Some("North Brabant")),
+    ("NL-NH", "Noord-Holland", Some("North Holland")),
+    ("NL-OV", "Overijssel", None),
+    ("NL-UT", "Utrecht", None),
+    ("NL-ZE", "Zeeland", None),
+    ("NL-ZH", "Zuid-Holland", Some("South Holland")),
+    ("DK-81", "Nordjylland", Some("North Denmark")),
+    ("DK-82", "Midtjylland", Some("Central Denmark")),
+    ("DK-83", "Syddanmark", Some("Southern Denmark")),
+    ("DK-84", "Hovedstaden", Some("Capital Region")),
+    ("DK-85", "Sjælland", Some("Zealand")),
+    ("GB-ENG", "England", None),
+    ("GB-SCT", "Scotland", None),
+    ("GB-WLS", "Wales", None),
+    ("GB-NIR", "Northern Ireland", None),
+    ("FR-ARA", "Auvergne-Rhône-Alpes", None),
+    ("FR-BFC", "Bourgogne-Franche-Comté", None),
+    ("FR-BRE", "Bretagne", Some("Brittany")),
+    ("FR-CVL", "Centre-Val de Loire", None),
+    ("FR-COR", "Corse", Some("Corsica")),
+    ("FR-GES", "Grand Est", None),
+    ("FR-HDF", "Hauts-de-France", None),
+    ("FR-IDF", "Île-de-France", None),
+    ("FR-NOR", "Normandie", Some("Normandy")),
+    ("FR-NAQ", "Nouvelle-Aquitaine", None),
+    ("FR-OCC", "Occitanie", None),
+    ("FR-PDL", "Pays de la Loire", None),
+    ("FR-PAC", "Provence-Alpes-Côte d'Azur", None),
+];
+
+/// Historical and informal names of regions players tend to use
+const REGION_ALIASES: &[(&str, &str)] = &[
+    ("PL-24", "Upper Silesia"),
+    ("PL-24", "Teschen Silesia"),
+    ("PL-24", "Cieszyn Silesia"),
+    ("CZ-80", "Czech Silesia"),
+];
+
+fn has_subdivisions(country_code: &str) -> bool {
+    SUBDIVISIONS.iter().any(|(code, _, _)| code.starts_with(country_code) && code.as_bytes()[2] == b'-')
+}
+
+/// ISO 3166-1 alpha-2 code of the country given either by code or by english name,
+/// i.e. `pl` => `PL`, `Poland` => `PL`
+pub fn normalize_country(input: &str) -> Option<&'static str> {
+    let input = input.trim();
+    let upper = input.to_uppercase();
+    let folded = fold(input);
+
+    COUNTRIES
+        .iter()
+        .find(|(code, _)| *code == upper)
+        .or_else(|| COUNTRIES.iter().find(|(_, name)| fold(name) == folded))
+        .map(|(code, _)| *code)
+}
+
+/// English name of the country given by ISO 3166-1 alpha-2 code
+pub fn country_name(country_code: &str) -> Option<&'static str> {
+    COUNTRIES
+        .iter()
+        .find(|(code, _)| *code == country_code)
+        .map(|(_, name)| *name)
+}
+
+/// ISO 3166-2 code of the region of the country given either by code or by name,
+/// i.e. `pl-24` => `PL-24`, `Upper Silesia` => `PL-24`
+pub fn normalize_region(country_code: &str, input: &str) -> Option<String> {
+    let input = input.trim();
+    let upper = input.to_uppercase();
+    let folded = fold(input);
+    let prefix = format!("{}-", country_code);
+
+    let known = SUBDIVISIONS
+        .iter()
+        .filter(|(code, _, _)| code.starts_with(&prefix))
+        .find(|(code, name, english)| {
+            *code == upper || fold(name) == folded || english.is_some_and(|english| fold(english) == folded)
+        })
+        .map(|(code, _, _)| code.to_string())
+        .or_else(|| {
+            REGION_ALIASES
+                .iter()
+                .find(|(code, name)| code.starts_with(&prefix) && fold(name) == folded)
+                .map(|(code, _)| code.to_string())
+        });
+
+    match known {
+        Some(code) => Some(code),
+        // subdivisions of the country are not known, accept anything shaped as a code
+        None if !has_subdivisions(country_code) && is_subdivision_code(&prefix, &upper) => Some(upper),
+        None => None,
+    }
+}
+
+fn is_subdivision_code(prefix: &str, code: &str) -> bool {
+    code.strip_prefix(prefix).is_some_and(|suffix| {
+        (1..=3).contains(&suffix.len()) && suffix.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Great-circle distance in meters between two points given in degrees
+pub fn distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Latitude and longitude ranges in degrees containing every point within `radius` meters,
+/// longitude range is unbounded when the box would wrap around a pole or the antimeridian
+pub fn bounding_box(lat: f64, lon: f64, radius: f64) -> ((f64, f64), (f64, f64)) {
+    let d_lat = (radius / EARTH_RADIUS).to_degrees();
+    let lat_range = ((lat - d_lat).max(-90.0), (lat + d_lat).min(90.0));
+    let cos_lat = lat_range.0.to_radians().cos().min(lat_range.1.to_radians().cos());
+
+    if cos_lat <= f64::EPSILON {
+        return (lat_range, (-180.0, 180.0));
+    }
+
+    let d_lon = (radius / (EARTH_RADIUS * cos_lat)).to_degrees();
+
+    if lon - d_lon < -180.0 || lon + d_lon > 180.0 {
+        (lat_range, (-180.0, 180.0))
+    } else {
+        (lat_range, (lon - d_lon, lon + d_lon))
+    }
+}
+
+pub fn validate_country_code(code: &str) -> Result<(), ValidationError> {
+    if COUNTRIES.iter().any(|(known, _)| *known == code) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("unknown ISO 3166-1 country code"))
+    }
+}
+
+pub fn validate_region_code(code: &str) -> Result<(), ValidationError> {
+    let is_valid = code
+        .get(..2)
+        .filter(|country_code| validate_country_code(country_code).is_ok())
+        .is_some_and(|country_code| normalize_region(country_code, code).as_deref() == Some(code));
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("unknown ISO 3166-2 region code"))
+    }
+}
+
+/// Normalizes country codes of players and places and fills region codes of players,
+/// values which can't be normalized are left as they are
+pub async fn normalize_players_and_places(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+
+    let players = sqlx::query!(
+        "SELECT uuid, country_code, region, region_code FROM players_cache WHERE erased_at IS NULL"
+    )
+    .fetch_all(&mut conn)
+    .await?;
+    let mut normalized = 0;
+
+    for player in &players {
+        let country_code = match normalize_country(&player.country_code) {
+            Some(country_code) => country_code,
+            None => {
+                warn!("player [{}] has unknown country [{}]", player.uuid, player.country_code);
+                continue;
+            }
+        };
+        let region_code = player
+            .region
+            .as_deref()
+            .and_then(|region| normalize_region(country_code, region));
+
+        if country_code != player.country_code || region_code != player.region_code {
+            sqlx::query!(
+                "UPDATE players_cache SET country_code = ?, region_code = ? WHERE uuid = ?",
+                country_code,
+                region_code,
+                player.uuid
+            )
+            .execute(&mut conn)
+            .await?;
+
+            normalized += 1;
+        }
+    }
+
+    let places = sqlx::query!("SELECT uuid, country_code FROM places")
+        .fetch_all(&mut conn)
+        .await?;
+
+    for place in &places {
+        match normalize_country(&place.country_code) {
+            Some(country_code) if country_code != place.country_code => {
+                sqlx::query!("UPDATE places SET country_code = ? WHERE uuid = ?", country_code, place.uuid)
+                    .execute(&mut conn)
+                    .await?;
+
+                normalized += 1;
+            }
+            Some(_) => {}
+            None => warn!("place [{}] has unknown country [{}]", place.uuid, place.country_code),
+        }
+    }
+
+    info!("normalized country and region of {} players and places", normalized);
+
+    Ok(())
+}
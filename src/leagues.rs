@@ -0,0 +1,764 @@
+use axum::{extract::Path, response::IntoResponse, routing::{get, post}, Json, Router};
+use hashbrown::HashSet;
+use rand::prelude::SliceRandom;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::Connection;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::{
+    app::AppError,
+    db::DatabaseConnection,
+    firebase, seating,
+    tournaments::{self, TournamentRules},
+    users,
+    validate::ValidatedJson,
+};
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/rankings/:ranking_uuid/leagues",
+            get(leagues_index).post(leagues_create),
+        )
+        .route(
+            "/rankings/:ranking_uuid/leagues/:league_uuid",
+            get(leagues_show),
+        )
+        .route(
+            "/rankings/:ranking_uuid/leagues/:league_uuid/entrants",
+            post(leagues_entrants_create),
+        )
+        .route(
+            "/rankings/:ranking_uuid/leagues/:league_uuid/match_days",
+            post(leagues_match_days_create),
+        )
+        .route(
+            "/rankings/:ranking_uuid/leagues/:league_uuid/fixtures",
+            post(leagues_fixtures_generate),
+        )
+        .route(
+            "/rankings/:ranking_uuid/leagues/:league_uuid/table",
+            get(leagues_table),
+        )
+}
+
+// players in roster of a team
+const TEAM_MAX_PLAYERS: usize = 8;
+
+/// Entrants of individual leagues are single players, of team leagues named teams
+/// which send one of their players to every game of a fixture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeagueKind {
+    Individual,
+    Team,
+}
+
+impl LeagueKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LeagueKind::Individual => "individual",
+            LeagueKind::Team => "team",
+        }
+    }
+}
+
+pub struct League {
+    pub uuid: String,
+    pub ranking_uuid: String,
+    pub kind: String,
+    pub creator_uid: String,
+    pub rules: TournamentRules,
+}
+
+impl League {
+    pub async fn fetch(
+        conn: &mut sqlx::SqliteConnection,
+        ranking_uuid: &str,
+        league_uuid: &str,
+    ) -> Result<Self, AppError> {
+        let row = sqlx::query!(
+            "SELECT uuid, ranking_uuid, kind, return_points, uma, creator_uid
+            FROM leagues WHERE ranking_uuid = ? AND uuid = ?",
+            ranking_uuid,
+            league_uuid,
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(AppError::LeagueNotFound)?;
+
+        Ok(Self {
+            uuid: row.uuid,
+            ranking_uuid: row.ranking_uuid,
+            kind: row.kind,
+            creator_uid: row.creator_uid,
+            rules: TournamentRules {
+                return_points: row.return_points,
+                uma: serde_json::from_str(&row.uma)?,
+                tie_breakers: tournaments::DEFAULT_TIE_BREAKERS.to_vec(),
+            },
+        })
+    }
+
+    /// Organiser is the user who created the league, admins can manage any league
+    pub fn authorize(
+        &self,
+        current_user: &users::CurrentUser,
+        current_admin: &Option<users::CurrentAdmin>,
+    ) -> Result<(), AppError> {
+        if current_admin.is_some() || current_user.user_uid == self.creator_uid {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden)
+        }
+    }
+}
+
+/// Fails unless the league fixture is in the ranking and seated players are one player
+/// of every entrant of the fixture
+pub async fn check_fixture_players(
+    conn: &mut sqlx::SqliteConnection,
+    ranking_uuid: &str,
+    league_fixture_uuid: &str,
+    players_uuids: &[String],
+) -> Result<(), AppError> {
+    let fixture = sqlx::query!(
+        "SELECT f.league_uuid, f.entrant1_uuid, f.entrant2_uuid, f.entrant3_uuid, f.entrant4_uuid
+        FROM league_fixtures f
+        INNER JOIN leagues l ON l.uuid = f.league_uuid
+        WHERE f.uuid = ? AND l.ranking_uuid = ?",
+        league_fixture_uuid,
+        ranking_uuid,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::LeagueFixtureNotFound)?;
+
+    let mut entrants = [
+        fixture.entrant1_uuid,
+        fixture.entrant2_uuid,
+        fixture.entrant3_uuid,
+        fixture.entrant4_uuid,
+    ]
+    .into_iter()
+    .collect::<HashSet<_>>();
+
+    for player_uuid in players_uuids {
+        let entrant_uuid = sqlx::query_scalar!(
+            "SELECT entrant_uuid FROM league_entrant_players WHERE league_uuid = ? AND player_uuid = ?",
+            fixture.league_uuid,
+            player_uuid,
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        // every entrant is represented exactly once
+        if !entrant_uuid.is_some_and(|entrant_uuid| entrants.remove(&entrant_uuid)) {
+            return Err(AppError::LeagueFixturePlayersMismatch);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_leagues_create_input"))]
+pub struct LeaguesCreate {
+    #[validate(length(min = 1, max = 64))]
+    name: String,
+    kind: LeagueKind,
+    #[validate(range(min = 0, max = 100000))]
+    return_points: Option<i64>,
+    #[validate(custom = "tournaments::validate_uma")]
+    uma: Option<Vec<i64>>,
+    starts_at: Option<i64>,
+    ends_at: Option<i64>,
+}
+
+fn validate_leagues_create_input(input: &LeaguesCreate) -> Result<(), ValidationError> {
+    match (input.starts_at, input.ends_at) {
+        (Some(starts_at), Some(ends_at)) if ends_at < starts_at => {
+            Err(ValidationError::new("league can't end before it starts"))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub async fn leagues_create(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    ValidatedJson(input): ValidatedJson<LeaguesCreate>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+    let return_points = input.return_points.unwrap_or(tournaments::DEFAULT_RETURN_POINTS);
+    let uma = serde_json::to_string(&input.uma.unwrap_or_else(|| tournaments::DEFAULT_UMA.to_vec()))?;
+    let kind = input.kind.as_str();
+
+    sqlx::query_scalar!(
+        "SELECT 1 FROM rankings_cache WHERE uuid = ? AND deleted_at IS NULL",
+        ranking_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::RankingNotFound)?;
+
+    sqlx::query!(
+        "INSERT INTO leagues (
+            uuid, ranking_uuid, name, kind, return_points, uma, creator_uid, starts_at, ends_at, created_at
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))",
+        uuid,
+        ranking_uuid,
+        input.name,
+        kind,
+        return_points,
+        uma,
+        current_user.user_uid,
+        input.starts_at,
+        input.ends_at,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    league_json(&mut conn, &ranking_uuid, &uuid).await
+}
+
+pub async fn leagues_index(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let data = sqlx::query!(
+        r#"SELECT
+            l.uuid, l.name, l.kind, l.starts_at, l.ends_at, l.created_at,
+            (SELECT COUNT(*) FROM league_entrants e WHERE e.league_uuid = l.uuid) as "entrants_count!: i64"
+        FROM leagues l
+        WHERE l.ranking_uuid = ?
+        ORDER BY l.created_at DESC
+        LIMIT 50"#,
+        ranking_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": data.iter().map(|row| {
+            json!({
+                "uuid": row.uuid,
+                "name": row.name,
+                "kind": row.kind,
+                "entrants_count": row.entrants_count,
+                "starts_at": row.starts_at,
+                "ends_at": row.ends_at,
+                "created_at": row.created_at,
+            })
+        }).collect::<Vec<_>>(),
+        "count": data.len(),
+    })))
+}
+
+pub async fn leagues_show(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path((ranking_uuid, league_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    league_json(&mut conn, &ranking_uuid, &league_uuid).await
+}
+
+/// League with entrants and match days with their fixtures
+async fn league_json(
+    conn: &mut sqlx::SqliteConnection,
+    ranking_uuid: &str,
+    league_uuid: &str,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let league = sqlx::query!(
+        "SELECT uuid, name, kind, return_points, uma, starts_at, ends_at, created_at
+        FROM leagues WHERE ranking_uuid = ? AND uuid = ?",
+        ranking_uuid,
+        league_uuid,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::LeagueNotFound)?;
+
+    let entrants = sqlx::query!(
+        "SELECT uuid, name, created_at FROM league_entrants
+        WHERE league_uuid = ?
+        ORDER BY created_at ASC, rowid ASC",
+        league_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let entrant_players = sqlx::query!(
+        "SELECT ep.entrant_uuid, ep.player_uuid, p.nickname
+        FROM league_entrant_players ep
+        LEFT JOIN players_cache p ON p.uuid = ep.player_uuid
+        WHERE ep.league_uuid = ?
+        ORDER BY ep.rowid ASC",
+        league_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let match_days = sqlx::query!(
+        "SELECT uuid, number, place_uuid, scheduled_at, created_at FROM league_match_days
+        WHERE league_uuid = ?
+        ORDER BY number ASC",
+        league_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let fixtures = sqlx::query!(
+        r#"SELECT
+            f.uuid, f.match_day_uuid, f.entrant1_uuid, f.entrant2_uuid, f.entrant3_uuid, f.entrant4_uuid,
+            (SELECT COUNT(*) FROM game_sessions gs
+                WHERE gs.league_fixture_uuid = f.uuid AND gs.is_league_game = 1) as "game_sessions_count!: i64"
+        FROM league_fixtures f
+        WHERE f.league_uuid = ?
+        ORDER BY f.rowid ASC"#,
+        league_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "uuid": league.uuid,
+                "name": league.name,
+                "kind": league.kind,
+                "return_points": league.return_points,
+                "uma": serde_json::from_str::<serde_json::Value>(&league.uma)?,
+                "starts_at": league.starts_at,
+                "ends_at": league.ends_at,
+                "created_at": league.created_at,
+                "$entrants": json!({
+                    "items": entrants.iter().map(|entrant| {
+                        let players = entrant_players
+                            .iter()
+                            .filter(|row| row.entrant_uuid == entrant.uuid)
+                            .collect::<Vec<_>>();
+
+                        json!({
+                            "uuid": entrant.uuid,
+                            "name": entrant.name,
+                            "created_at": entrant.created_at,
+                            "$players": json!({
+                                "items": players.iter().map(|row| {
+                                    json!({
+                                        "player_uuid": row.player_uuid,
+                                        "nickname": row.nickname,
+                                    })
+                                }).collect::<Vec<_>>(),
+                                "count": players.len(),
+                            }),
+                        })
+                    }).collect::<Vec<_>>(),
+                    "count": entrants.len(),
+                }),
+                "$match_days": json!({
+                    "items": match_days.iter().map(|match_day| {
+                        let match_day_fixtures = fixtures
+                            .iter()
+                            .filter(|row| row.match_day_uuid == match_day.uuid)
+                            .collect::<Vec<_>>();
+
+                        json!({
+                            "uuid": match_day.uuid,
+                            "number": match_day.number,
+                            "place_uuid": match_day.place_uuid,
+                            "scheduled_at": match_day.scheduled_at,
+                            "created_at": match_day.created_at,
+                            "$fixtures": json!({
+                                "items": match_day_fixtures.iter().map(|row| {
+                                    json!({
+                                        "uuid": row.uuid,
+                                        "entrants_uuids": [
+                                            row.entrant1_uuid, row.entrant2_uuid, row.entrant3_uuid, row.entrant4_uuid,
+                                        ],
+                                        "game_sessions_count": row.game_sessions_count,
+                                    })
+                                }).collect::<Vec<_>>(),
+                                "count": match_day_fixtures.len(),
+                            }),
+                        })
+                    }).collect::<Vec<_>>(),
+                    "count": match_days.len(),
+                }),
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_leagues_entrants_create_input"))]
+pub struct LeaguesEntrantsCreate {
+    // name of the team, individual entrants are not named
+    #[validate(length(min = 1, max = 64))]
+    name: Option<String>,
+    players_uuids: Vec<String>,
+}
+
+fn validate_leagues_entrants_create_input(input: &LeaguesEntrantsCreate) -> Result<(), ValidationError> {
+    let is_valid = (1..=TEAM_MAX_PLAYERS).contains(&input.players_uuids.len())
+        && input
+            .players_uuids
+            .iter()
+            .enumerate()
+            .all(|(index, uuid)| {
+                uuid.len() == crate::app::UUID_STRLEN && !input.players_uuids[..index].contains(uuid)
+            });
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid or repeated players"))
+    }
+}
+
+/// Enters a player, or a team with its roster, a player can be entered only once per league
+pub async fn leagues_entrants_create(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, league_uuid)): Path<(String, String)>,
+    ValidatedJson(input): ValidatedJson<LeaguesEntrantsCreate>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let league = League::fetch(&mut conn, &ranking_uuid, &league_uuid).await?;
+    let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+
+    league.authorize(&current_user, &current_admin)?;
+
+    // shape of the entrant depends on kind of the league, so it can't be validated upfront
+    let mut errors = ValidationErrors::new();
+
+    if league.kind == LeagueKind::Individual.as_str() {
+        if input.players_uuids.len() != 1 {
+            errors.add("players_uuids", ValidationError::new("individual entrant is a single player"));
+        }
+        if input.name.is_some() {
+            errors.add("name", ValidationError::new("individual entrant is not named"));
+        }
+    } else if input.name.is_none() {
+        errors.add("name", ValidationError::new("team must be named"));
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::ValidationError(errors));
+    }
+
+    let mut tx = conn.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO league_entrants (uuid, league_uuid, name, created_at)
+        VALUES (?, ?, ?, strftime('%s', 'now'))",
+        uuid,
+        league.uuid,
+        input.name,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    for player_uuid in &input.players_uuids {
+        sqlx::query_scalar!(
+            "SELECT 1 FROM players_cache WHERE ranking_uuid = ? AND uuid = ? AND deleted_at IS NULL",
+            league.ranking_uuid,
+            player_uuid,
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(AppError::PlayerNotFound)?;
+
+        sqlx::query!(
+            "INSERT INTO league_entrant_players (league_uuid, entrant_uuid, player_uuid, created_at)
+            VALUES (?, ?, ?, strftime('%s', 'now'))
+            ON CONFLICT (league_uuid, player_uuid) DO NOTHING",
+            league.uuid,
+            uuid,
+            player_uuid,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected()
+        .eq(&1)
+        .then_some(())
+        .ok_or(AppError::LeaguePlayerAlreadyEntered)?;
+    }
+
+    tx.commit().await?;
+
+    league_json(&mut conn, &ranking_uuid, &league_uuid).await
+}
+
+#[derive(Deserialize, Validate)]
+pub struct LeaguesMatchDaysCreate {
+    #[validate(length(equal = 36))]
+    place_uuid: String,
+    scheduled_at: i64,
+}
+
+/// Schedules the next match day at the venue, fixtures are generated separately
+pub async fn leagues_match_days_create(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, league_uuid)): Path<(String, String)>,
+    ValidatedJson(input): ValidatedJson<LeaguesMatchDaysCreate>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let league = League::fetch(&mut conn, &ranking_uuid, &league_uuid).await?;
+    let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+
+    league.authorize(&current_user, &current_admin)?;
+
+    sqlx::query_scalar!("SELECT 1 FROM places WHERE uuid = ?", input.place_uuid)
+        .fetch_optional(&mut conn)
+        .await?
+        .ok_or(AppError::PlaceNotFound)?;
+
+    sqlx::query!(
+        "INSERT INTO league_match_days (uuid, league_uuid, number, place_uuid, scheduled_at, created_at)
+        SELECT ?1, ?2, COALESCE(MAX(number), 0) + 1, ?3, ?4, strftime('%s', 'now')
+        FROM league_match_days WHERE league_uuid = ?2",
+        uuid,
+        league.uuid,
+        input.place_uuid,
+        input.scheduled_at,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    league_json(&mut conn, &ranking_uuid, &league_uuid).await
+}
+
+/// Generates fixtures of every match day which has none yet, in order of match days.
+/// When the number of entrants allows an exact round-robin (see `seating::round_robin`) and
+/// earlier match days follow it, everyone meets everyone once before the round-robin repeats.
+/// Otherwise entrants are grouped in fours minimising repeated meetings with earlier match days,
+/// and entrants not filling a table get a bye
+pub async fn leagues_fixtures_generate(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, league_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let league = League::fetch(&mut conn, &ranking_uuid, &league_uuid).await?;
+
+    league.authorize(&current_user, &current_admin)?;
+
+    let mut tx = conn.begin().await?;
+
+    let entrants = sqlx::query_scalar!(
+        "SELECT uuid FROM league_entrants WHERE league_uuid = ? ORDER BY created_at ASC, rowid ASC",
+        league.uuid,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    if entrants.len() < 4 {
+        return Err(AppError::LeagueNotEnoughEntrants);
+    }
+
+    let match_days = sqlx::query!(
+        r#"SELECT
+            uuid,
+            EXISTS(SELECT 1 FROM league_fixtures f WHERE f.match_day_uuid = d.uuid) as "has_fixtures!: bool"
+        FROM league_match_days d
+        WHERE league_uuid = ?
+        ORDER BY number ASC"#,
+        league.uuid,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    let fixtures = sqlx::query!(
+        "SELECT match_day_uuid, entrant1_uuid, entrant2_uuid, entrant3_uuid, entrant4_uuid
+        FROM league_fixtures WHERE league_uuid = ?",
+        league.uuid,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    // entrants missing from fixtures of a generated match day had a bye
+    let mut history = seating::SeatingHistory::default();
+    let mut rounds = seating::round_robin(&entrants);
+
+    for (index, match_day) in match_days.iter().enumerate().filter(|(_, match_day)| match_day.has_fixtures) {
+        let mut seated = HashSet::new();
+        let mut tables = HashSet::new();
+
+        for row in fixtures.iter().filter(|row| row.match_day_uuid == match_day.uuid) {
+            let table = [
+                row.entrant1_uuid.clone(),
+                row.entrant2_uuid.clone(),
+                row.entrant3_uuid.clone(),
+                row.entrant4_uuid.clone(),
+            ];
+
+            history.add_table(&table);
+            seated.extend(table.clone());
+            tables.insert(sorted(table.to_vec()));
+        }
+
+        for entrant_uuid in entrants.iter().filter(|entrant_uuid| !seated.contains(*entrant_uuid)) {
+            history.add_bye(entrant_uuid);
+        }
+
+        // round-robin is only continued when nothing was generated without it
+        let is_scheduled = rounds.as_ref().is_some_and(|rounds| {
+            let round = &rounds[index % rounds.len()];
+
+            round.iter().map(|table| sorted(table.clone())).collect::<HashSet<_>>() == tables
+        });
+        if !is_scheduled {
+            rounds = None;
+        }
+    }
+
+    for (index, match_day) in match_days.iter().enumerate().filter(|(_, match_day)| !match_day.has_fixtures) {
+        let (tables, byes) = match &rounds {
+            Some(rounds) => {
+                let tables = rounds[index % rounds.len()]
+                    .iter()
+                    .map(|table| seating::assign_seats(table.clone(), &history))
+                    .collect::<Vec<_>>();
+
+                (tables, Vec::new())
+            }
+            None => {
+                let mut match_day_entrants = entrants.clone();
+                match_day_entrants.shuffle(&mut rand::thread_rng());
+
+                let byes = seating::pick_byes(&match_day_entrants, match_day_entrants.len() % 4, &history);
+                match_day_entrants.retain(|entrant_uuid| !byes.contains(entrant_uuid));
+
+                (seating::seat(&match_day_entrants, &history, false), byes)
+            }
+        };
+
+        for table in tables {
+            let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+
+            sqlx::query!(
+                "INSERT INTO league_fixtures (
+                    uuid, league_uuid, match_day_uuid, entrant1_uuid, entrant2_uuid, entrant3_uuid, entrant4_uuid,
+                    created_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))",
+                uuid,
+                league.uuid,
+                match_day.uuid,
+                table[0],
+                table[1],
+                table[2],
+                table[3],
+            )
+            .execute(&mut tx)
+            .await?;
+
+            history.add_table(&table);
+        }
+
+        for entrant_uuid in &byes {
+            history.add_bye(entrant_uuid);
+        }
+    }
+
+    tx.commit().await?;
+
+    league_json(&mut conn, &ranking_uuid, &league_uuid).await
+}
+
+fn sorted(mut table: Vec<String>) -> Vec<String> {
+    table.sort();
+    table
+}
+
+/// League table from final scores of computed league games, sessions not flagged
+/// as league games don't count even when linked to a fixture
+pub async fn leagues_table(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path((ranking_uuid, league_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let league = League::fetch(&mut conn, &ranking_uuid, &league_uuid).await?;
+
+    let entrants = sqlx::query!(
+        "SELECT uuid, name FROM league_entrants WHERE league_uuid = ?",
+        league.uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let entrant_players = sqlx::query!(
+        "SELECT entrant_uuid, player_uuid FROM league_entrant_players WHERE league_uuid = ? ORDER BY rowid ASC",
+        league.uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let results = sqlx::query!(
+        "SELECT ep.entrant_uuid, r.points, r.placement
+        FROM game_session_results_cache r
+        INNER JOIN game_sessions gs ON gs.uuid = r.game_session_uuid
+        INNER JOIN league_fixtures f ON f.uuid = gs.league_fixture_uuid
+        INNER JOIN league_entrant_players ep ON ep.league_uuid = f.league_uuid AND ep.player_uuid = r.player_uuid
+        WHERE f.league_uuid = ? AND gs.is_league_game = 1 AND r.ranking_uuid = ?",
+        league.uuid,
+        league.ranking_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?
+    .into_iter()
+    .map(|row| (row.entrant_uuid, row.points, row.placement))
+    .collect::<Vec<_>>();
+
+    let uuids = entrants.iter().map(|row| row.uuid.clone()).collect::<Vec<_>>();
+    let standings = tournaments::standings(&league.rules, &uuids, &results);
+
+    Ok(Json(json!({
+        "items": standings.iter().map(|standing| {
+            let name = entrants
+                .iter()
+                .find(|row| row.uuid == standing.uuid)
+                .and_then(|row| row.name.as_ref());
+            let players_uuids = entrant_players
+                .iter()
+                .filter(|row| row.entrant_uuid == standing.uuid)
+                .map(|row| &row.player_uuid)
+                .collect::<Vec<_>>();
+
+            json!({
+                "position": standing.position,
+                "entrant_uuid": standing.uuid,
+                "name": name,
+                "players_uuids": players_uuids,
+                "games_count": standing.games,
+                "points": standing.tournament_points,
+                "total_points": standing.total_points,
+                "placements": standing.placements,
+            })
+        }).collect::<Vec<_>>(),
+        "count": standings.len(),
+    })))
+}
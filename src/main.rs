@@ -1,8 +1,14 @@
+mod achievements;
+mod admin;
 mod app;
+mod compute;
 mod config;
 mod db;
 mod firebase;
 mod games;
+mod gdpr;
+mod geo;
+mod leagues;
 mod places;
 mod users;
 mod validate;
@@ -10,13 +16,23 @@ mod game_events;
 mod players;
 mod rankings;
 mod ranks;
+mod rating;
+mod scoring;
+mod search;
+mod seating;
+mod stats;
+mod sync;
+mod teams;
+mod tournaments;
 
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::compute::ComputeService;
 use crate::firebase::FirebaseTokenService;
+use crate::sync::SyncService;
 use app::AppError;
 use axum::handler::Handler;
 use axum::response::IntoResponse;
@@ -42,6 +58,47 @@ fn spawn_worker_thread(firebase: Arc<FirebaseTokenService>) -> tokio::task::Join
     })
 }
 
+fn spawn_compute_thread(compute: Arc<ComputeService>, interval: std::time::Duration) -> tokio::task::JoinHandle<Infallible> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = compute.compute_dirty_rankings().await {
+                error!("failed to compute rankings: {}", e);
+            }
+            debug!("will compute rankings in at most {:?}", interval);
+
+            compute.wait_for_recompute_request(interval).await;
+        }
+    })
+}
+
+fn spawn_sync_thread(sync: Arc<SyncService>, interval: std::time::Duration) -> tokio::task::JoinHandle<Infallible> {
+    tokio::spawn(async move {
+        loop {
+            if sync.is_enabled() {
+                if let Err(e) = sync.sync(false).await {
+                    error!("failed to sync with upstream: {}", e);
+                }
+            }
+            debug!("will sync with upstream in {:?}", interval);
+
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
+
+fn spawn_place_rank_thread(pool: sqlx::SqlitePool, interval: std::time::Duration) -> tokio::task::JoinHandle<Infallible> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = places::compute_places_rank(&pool).await {
+                error!("failed to compute places rank: {}", e);
+            }
+            debug!("will compute places rank in {:?}", interval);
+
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
+
 #[tokio::main]
 async fn main() {
     let config = config::init_config();
@@ -51,10 +108,27 @@ async fn main() {
 
     let pool = db::init_db(&config).await;
 
+    search::backfill_players_search_terms(&pool)
+        .await
+        .expect("could not fill players search terms");
+    search::backfill_places_search(&pool)
+        .await
+        .expect("could not index places");
+    geo::normalize_players_and_places(&pool)
+        .await
+        .expect("could not normalize countries and regions");
+
     let firebase = Arc::new(FirebaseTokenService::new(
         config.firebase_project_id.clone(),
     ));
 
+    let compute = Arc::new(ComputeService::new(pool.clone()));
+    let sync = Arc::new(SyncService::new(
+        pool.clone(),
+        compute.clone(),
+        config.sync_source.clone().map(std::path::PathBuf::from),
+    ));
+
     let cors = CorsLayer::new()
         .allow_methods(Any)
         .allow_headers([http::header::CONTENT_TYPE, http::header::AUTHORIZATION])
@@ -72,17 +146,39 @@ async fn main() {
                 .merge(players::router())
                 .merge(ranks::router())
                 .merge(users::router())
+                .merge(gdpr::router())
+                .merge(admin::router())
+                .merge(achievements::router())
+                .merge(tournaments::router())
+                .merge(leagues::router())
+                .merge(teams::router())
                 .merge(rankings::router())
                 .layer(&cors),
         )
         .layer(&cors)
         .layer(CompressionLayer::new())
-        .layer(Extension(pool))
+        .layer(Extension(pool.clone()))
         .layer(Extension(firebase.clone()))
+        .layer(Extension(compute.clone()))
+        .layer(Extension(sync.clone()))
         .fallback(not_found.layer(CompressionLayer::new()).layer(&cors).into_service());
     let addr = SocketAddr::from_str(&config.bind_interface).expect("malformed bind_interface str");
 
     let worker_thread = spawn_worker_thread(firebase);
+    let compute_thread = spawn_compute_thread(
+        compute,
+        std::time::Duration::from_secs(config.ranking_compute_interval),
+    );
+
+    let sync_thread = spawn_sync_thread(
+        sync,
+        std::time::Duration::from_secs(config.sync_interval),
+    );
+
+    let place_rank_thread = spawn_place_rank_thread(
+        pool,
+        std::time::Duration::from_secs(config.place_rank_interval),
+    );
 
     info!("listening on {}", addr);
 
@@ -92,6 +188,12 @@ async fn main() {
         
     worker_thread.abort();
     worker_thread.await.unwrap_err();
+    compute_thread.abort();
+    compute_thread.await.unwrap_err();
+    sync_thread.abort();
+    sync_thread.await.unwrap_err();
+    place_rank_thread.abort();
+    place_rank_thread.await.unwrap_err();
 
     server.unwrap()
 }
@@ -1,24 +1,77 @@
-use axum::{Router, Json, routing::get, response::IntoResponse, handler::Handler};
+use axum::{extract::Path, Router, Json, routing::{get, patch, post}, response::IntoResponse, handler::Handler};
+use chrono::{Datelike, TimeZone, Timelike, Utc};
+use hashbrown::{HashMap, HashSet};
 use serde::{Deserialize};
+use sqlx::SqlitePool;
+use tracing::info;
 use serde_json::json;
 use tower_http::compression::CompressionLayer;
-use validator::{Validate};
+use validator::{Validate, ValidationError};
 
-use crate::{firebase, users, db::DatabaseConnection, app::{AppError}, validate::{ValidatedQuery}};
+use crate::{firebase, geo, search, users, db::DatabaseConnection, app::{AppError}, validate::{self, ValidatedJson, ValidatedQuery}};
 
 pub fn router() -> Router {
-    Router::new().route(
-        "/places",
-        get(places_index.layer(CompressionLayer::new()))
-    )
+    Router::new()
+        .route(
+            "/places",
+            get(places_index.layer(CompressionLayer::new()))
+                .post(places_create),
+        )
+        .route(
+            "/places/nearby",
+            get(places_nearby),
+        )
+        .route(
+            "/places/:place_uuid",
+            patch(places_update),
+        )
+        .route(
+            "/places/:place_uuid/archive",
+            post(places_archive),
+        )
+        .route(
+            "/places/:place_uuid/stats",
+            get(places_stats.layer(CompressionLayer::new())),
+        )
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaceType {
+    Club,
+    Cafe,
+    PrivateHome,
+    TournamentHall,
+}
+
+impl PlaceType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PlaceType::Club => "club",
+            PlaceType::Cafe => "cafe",
+            PlaceType::PrivateHome => "private_home",
+            PlaceType::TournamentHall => "tournament_hall",
+        }
+    }
 }
 
+/// Type of places created before typed categories, can't be set through the api
+pub const PLACE_TYPE_UNKNOWN: &str = "unknown";
+
+/// Moderation state of the place, only approved places are listed
+pub const PLACE_STATUS_PENDING: &str = "pending";
+pub const PLACE_STATUS_APPROVED: &str = "approved";
+pub const PLACE_STATUS_REJECTED: &str = "rejected";
+
 #[derive(Deserialize, Validate)]
 pub struct PlacesIndex {
     // limit max length of name to 16 chars so at most little abuse can be done
-    // also require at least three chars to start filtering places
+    // also require at least three chars to start filtering places.
+    // matches words of name, street and city
     #[validate(length(min = 3, max = 16))]
-    name: Option<String>
+    name: Option<String>,
+    #[serde(rename = "type")]
+    place_type: Option<PlaceType>,
 }
 #[derive(Deserialize)]
 struct PlacesModel {
@@ -26,7 +79,10 @@ struct PlacesModel {
     name: String,
     street: Option<String>,
     city: String,
-    country_code: String
+    country_code: String,
+    place_type: String,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
 }
 pub async fn places_index(
     _claims: firebase::FirebaseClaims,
@@ -35,28 +91,46 @@ pub async fn places_index(
     DatabaseConnection(conn): DatabaseConnection,
 ) -> Result<impl IntoResponse, AppError> {
     let mut conn = conn;
+    let place_type = input.place_type.map(|place_type| place_type.as_str());
 
-    let data = match input.name {
-        Some(name) => {
-            // LIKE 'name%' is more efficient than LIKE '%name%' or LIKE '%name' because
-            // database can use index to speed up the query, as opposed to the latter two
-            let escaped = format!("{}%", name.replace('%', "\\%").replace('?', "\\?"));
+    let data = match input.name.as_deref().map(search::match_query) {
+        // any word of name, street or city starting with any of given words, matches in name
+        // weight the most. bm25 is lower for better matches
+        Some(Some(query)) => {
             sqlx::query_as!(
                 PlacesModel,
-                "SELECT uuid, name, street, city, country_code FROM places
-                WHERE places.name LIKE ?
-                ORDER BY places.name ASC
-                LIMIT 5",
-                escaped,
+                r#"SELECT
+                    p.uuid as "uuid!", p.name as "name!", p.street, p.city as "city!",
+                    p.country_code as "country_code!", p.type as "place_type!",
+                    p.latitude, p.longitude
+                FROM places_search s
+                INNER JOIN places p ON p.uuid = s.place_uuid
+                WHERE places_search MATCH ?1 AND p.status = ?2 AND p.archived_at IS NULL
+                    AND (?3 IS NULL OR p.type = ?3)
+                ORDER BY bm25(places_search, 0.0, 10.0, 1.0, 2.0) ASC, p.rank < 0 ASC, p.rank ASC
+                LIMIT 5"#,
+                query,
+                PLACE_STATUS_APPROVED,
+                place_type,
             ).fetch_all(&mut conn)
             .await
         },
+        // only punctuation, nothing to search for
+        Some(None) => Ok(Vec::new()),
+        // without name the most relevant places are suggested, places without recent games last
         None => {
             sqlx::query_as!(
                 PlacesModel,
-                "SELECT uuid, name, street, city, country_code FROM places
-                ORDER BY places.name ASC
-                LIMIT 5"
+                r#"SELECT
+                    uuid as "uuid!", name as "name!", street, city as "city!",
+                    country_code as "country_code!", type as "place_type!", latitude, longitude
+                FROM places
+                WHERE status = ?1 AND archived_at IS NULL
+                    AND (?2 IS NULL OR type = ?2)
+                ORDER BY places.rank < 0 ASC, places.rank ASC, places.name ASC
+                LIMIT 5"#,
+                PLACE_STATUS_APPROVED,
+                place_type,
             ).fetch_all(&mut conn)
             .await
         }
@@ -70,8 +144,594 @@ pub async fn places_index(
                 "street": row.street,
                 "city": row.city,
                 "country_code": row.country_code,
+                "type": row.place_type,
+                "latitude": row.latitude,
+                "longitude": row.longitude,
             })
         }).collect::<Vec<_>>(),
         "count": data.len(),
     })))
 }
+
+#[derive(Deserialize, Validate)]
+pub struct PlacesNearby {
+    #[validate(range(min = -90.0, max = 90.0))]
+    lat: f64,
+    #[validate(range(min = -180.0, max = 180.0))]
+    lon: f64,
+    // meters, large radius would scan most of the places
+    #[validate(range(min = 1.0, max = 50000.0))]
+    radius: Option<f64>,
+}
+
+/// Closest places within the radius, nearest first
+pub async fn places_nearby(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    ValidatedQuery(input): ValidatedQuery<PlacesNearby>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    const DEFAULT_RADIUS: f64 = 1000.0;
+    const LIMIT: usize = 5;
+    let mut conn = conn;
+    let radius = input.radius.unwrap_or(DEFAULT_RADIUS);
+    let ((min_lat, max_lat), (min_lon, max_lon)) = geo::bounding_box(input.lat, input.lon, radius);
+
+    // bounding box is a cheap prefilter, exact distance is checked below
+    let data = sqlx::query_as!(
+        PlacesModel,
+        r#"SELECT
+            uuid as "uuid!", name as "name!", street, city as "city!",
+            country_code as "country_code!", type as "place_type!", latitude, longitude
+        FROM places
+        WHERE status = ?1 AND archived_at IS NULL
+            AND latitude BETWEEN ?2 AND ?3 AND longitude BETWEEN ?4 AND ?5"#,
+        PLACE_STATUS_APPROVED,
+        min_lat,
+        max_lat,
+        min_lon,
+        max_lon,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let mut nearby = data
+        .iter()
+        .filter_map(|row| {
+            let distance = geo::distance(input.lat, input.lon, row.latitude?, row.longitude?);
+
+            (distance <= radius).then_some((distance, row))
+        })
+        .collect::<Vec<_>>();
+    nearby.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    nearby.truncate(LIMIT);
+
+    Ok(Json(json!({
+        "items": nearby.iter().map(|(distance, row)| {
+            json!({
+                "uuid": row.uuid,
+                "name": row.name,
+                "street": row.street,
+                "city": row.city,
+                "country_code": row.country_code,
+                "type": row.place_type,
+                "latitude": row.latitude,
+                "longitude": row.longitude,
+                "distance": distance.round() as i64,
+            })
+        }).collect::<Vec<_>>(),
+        "count": nearby.len(),
+    })))
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_places_create_input"))]
+pub struct PlacesCreate {
+    #[validate(length(min = 3, max = 64))]
+    name: String,
+    #[validate(length(min = 1, max = 128))]
+    street: Option<String>,
+    #[validate(length(min = 1, max = 64))]
+    city: String,
+    #[validate(custom = "geo::validate_country_code")]
+    country_code: String,
+    #[serde(rename = "type")]
+    place_type: PlaceType,
+    #[validate(range(min = -90.0, max = 90.0))]
+    latitude: Option<f64>,
+    #[validate(range(min = -180.0, max = 180.0))]
+    longitude: Option<f64>,
+}
+
+fn validate_places_create_input(input: &PlacesCreate) -> Result<(), ValidationError> {
+    validate_location(input.latitude, input.longitude)
+}
+
+// location is either complete or not known at all
+fn validate_location(latitude: Option<f64>, longitude: Option<f64>) -> Result<(), ValidationError> {
+    if latitude.is_some() == longitude.is_some() {
+        Ok(())
+    } else {
+        Err(ValidationError::new("latitude and longitude must be given together"))
+    }
+}
+
+/// Submits the place to moderation queue, it's listed once approved by an admin
+pub async fn places_create(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    ValidatedJson(input): ValidatedJson<PlacesCreate>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+    let name = input.name.trim();
+    let place_type = input.place_type.as_str();
+
+    let result = sqlx::query!(
+        "INSERT INTO places (
+            uuid, name, street, city, country_code, type, latitude, longitude, status, creator_uid, created_at
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))",
+        uuid,
+        name,
+        input.street,
+        input.city,
+        input.country_code,
+        place_type,
+        input.latitude,
+        input.longitude,
+        PLACE_STATUS_PENDING,
+        current_user.user_uid,
+    )
+    .execute(&mut conn)
+    .await;
+
+    if let Err(err) = result {
+        return Err(name_conflict(&mut conn, name, err).await);
+    }
+    search::index_place(&mut conn, &uuid).await?;
+
+    places_show(&mut conn, &uuid).await
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_places_update_input"))]
+pub struct PlacesUpdate {
+    #[validate(length(min = 3, max = 64))]
+    name: Option<String>,
+    #[serde(default, deserialize_with = "validate::double_option")]
+    #[validate(length(min = 1, max = 128))]
+    street: Option<Option<String>>,
+    #[validate(length(min = 1, max = 64))]
+    city: Option<String>,
+    #[validate(custom = "geo::validate_country_code")]
+    country_code: Option<String>,
+    #[serde(rename = "type")]
+    place_type: Option<PlaceType>,
+    #[serde(default, deserialize_with = "validate::double_option")]
+    #[validate(range(min = -90.0, max = 90.0))]
+    latitude: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "validate::double_option")]
+    #[validate(range(min = -180.0, max = 180.0))]
+    longitude: Option<Option<f64>>,
+}
+
+fn validate_places_update_input(input: &PlacesUpdate) -> Result<(), ValidationError> {
+    // location is updated or cleared as a whole
+    match (input.latitude, input.longitude) {
+        (Some(latitude), Some(longitude)) => validate_location(latitude, longitude),
+        (None, None) => Ok(()),
+        _ => Err(ValidationError::new("latitude and longitude must be given together")),
+    }
+}
+
+/// Updates given fields of the place, `street` and location are cleared by explicit null.
+/// admins can update any place, the user who submitted the place only while it's pending
+pub async fn places_update(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path(place_uuid): Path<String>,
+    ValidatedJson(input): ValidatedJson<PlacesUpdate>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    authorize(&mut conn, &place_uuid, &current_user, &current_admin).await?;
+
+    let name = input.name.as_deref().map(str::trim);
+    let place_type = input.place_type.map(|place_type| place_type.as_str());
+    let is_street_set = input.street.is_some();
+    let street = input.street.flatten();
+    let is_location_set = input.latitude.is_some();
+    let latitude = input.latitude.flatten();
+    let longitude = input.longitude.flatten();
+
+    let result = sqlx::query!(
+        "UPDATE places SET
+            name = COALESCE(?1, name), city = COALESCE(?2, city),
+            country_code = COALESCE(?3, country_code), type = COALESCE(?4, type),
+            street = CASE WHEN ?5 THEN ?6 ELSE street END,
+            latitude = CASE WHEN ?7 THEN ?8 ELSE latitude END,
+            longitude = CASE WHEN ?7 THEN ?9 ELSE longitude END,
+            updated_at = strftime('%s', 'now')
+        WHERE uuid = ?10",
+        name,
+        input.city,
+        input.country_code,
+        place_type,
+        is_street_set,
+        street,
+        is_location_set,
+        latitude,
+        longitude,
+        place_uuid,
+    )
+    .execute(&mut conn)
+    .await;
+
+    if let Err(err) = result {
+        return Err(name_conflict(&mut conn, name.unwrap_or_default(), err).await);
+    }
+    search::index_place(&mut conn, &place_uuid).await?;
+
+    places_show(&mut conn, &place_uuid).await
+}
+
+/// Hides the place from listing, game sessions at the place are kept.
+/// the user who submitted the place can withdraw it while it's pending
+pub async fn places_archive(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path(place_uuid): Path<String>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    authorize(&mut conn, &place_uuid, &current_user, &current_admin).await?;
+
+    sqlx::query!(
+        "UPDATE places SET archived_at = COALESCE(archived_at, strftime('%s', 'now')) WHERE uuid = ?",
+        place_uuid,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    places_show(&mut conn, &place_uuid).await
+}
+
+// calendar covers the last year, older games are counted in months only
+const CALENDAR_DAYS: i64 = 365;
+const TOP_PLAYERS_LIMIT: usize = 10;
+
+/// Usage of the place derived from ended games hosted there, undone and hidden games
+/// are skipped. games are bucketed by start time in UTC, weekday 0 is monday
+pub async fn places_stats(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path(place_uuid): Path<String>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    sqlx::query_scalar!("SELECT 1 FROM places WHERE uuid = ?", place_uuid)
+        .fetch_optional(&mut conn)
+        .await?
+        .ok_or(AppError::PlaceNotFound)?;
+
+    let games = sqlx::query!(
+        r#"WITH games AS (
+            SELECT
+                gs.player1_uuid, gs.player2_uuid, gs.player3_uuid, gs.player4_uuid,
+                MIN(CASE WHEN e.event_type = 'start' THEN e.created_at END) as started_at,
+                COALESCE(MIN(CASE WHEN e.event_type = 'start' THEN e.created_at END), gs.created_at) as played_at,
+                MAX(CASE WHEN e.event_type = 'end' THEN e.created_at END) as ended_at
+            FROM game_sessions gs
+            INNER JOIN game_session_events e ON e.game_session_uuid = gs.uuid
+            WHERE gs.place_uuid = ? AND gs.is_hidden = 0
+            GROUP BY gs.uuid
+            HAVING SUM(e.event_type = 'end') > 0 AND SUM(e.event_type = 'undo_game') = 0
+        )
+        SELECT
+            player1_uuid, player2_uuid, player3_uuid, player4_uuid,
+            started_at as "started_at?: i64", played_at as "played_at!: i64", ended_at as "ended_at!: i64"
+        FROM games
+        ORDER BY played_at ASC"#,
+        place_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let calendar_since = Utc::now().naive_utc().date() - chrono::Duration::days(CALENDAR_DAYS - 1);
+    let mut months = Vec::<(String, i64)>::new();
+    let mut calendar = Vec::<(String, i64)>::new();
+    let mut weekdays = [0_i64; 7];
+    let mut hours = [0_i64; 24];
+    // players with dates they played at the place
+    let mut players = HashMap::<&str, (i64, HashSet<String>)>::new();
+    let mut durations = Vec::new();
+
+    for game in &games {
+        let time = match Utc.timestamp_opt(game.played_at, 0).single() {
+            Some(time) => time,
+            None => continue,
+        };
+        let date = time.naive_utc().date();
+        let month = time.format("%Y-%m").to_string();
+
+        match months.last_mut() {
+            Some((last, count)) if *last == month => *count += 1,
+            _ => months.push((month, 1)),
+        }
+        if date >= calendar_since {
+            let day = date.to_string();
+
+            match calendar.last_mut() {
+                Some((last, count)) if *last == day => *count += 1,
+                _ => calendar.push((day, 1)),
+            }
+        }
+        weekdays[time.weekday().num_days_from_monday() as usize] += 1;
+        hours[time.hour() as usize] += 1;
+
+        if let Some(started_at) = game.started_at {
+            durations.push(game.ended_at - started_at);
+        }
+
+        for player_uuid in [&game.player1_uuid, &game.player2_uuid, &game.player3_uuid, &game.player4_uuid] {
+            let (count, dates) = players.entry(player_uuid.as_str()).or_default();
+            *count += 1;
+            dates.insert(date.to_string());
+        }
+    }
+
+    // returning player came back on another day
+    let returning_players_count = players.values().filter(|(_, dates)| dates.len() > 1).count();
+    let average_duration = (!durations.is_empty())
+        .then(|| durations.iter().sum::<i64>() / durations.len() as i64);
+
+    let mut top_players = players
+        .iter()
+        .map(|(player_uuid, (count, _))| (*player_uuid, *count))
+        .collect::<Vec<_>>();
+    top_players.sort_by(|(a_uuid, a), (b_uuid, b)| b.cmp(a).then_with(|| a_uuid.cmp(b_uuid)));
+    top_players.truncate(TOP_PLAYERS_LIMIT);
+
+    let mut nicknames = HashMap::new();
+
+    for (player_uuid, _) in &top_players {
+        let nickname = sqlx::query_scalar!("SELECT nickname FROM players_cache WHERE uuid = ?", player_uuid)
+            .fetch_optional(&mut conn)
+            .await?
+            .flatten();
+
+        nicknames.insert(*player_uuid, nickname);
+    }
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "place_uuid": place_uuid,
+                "games_count": games.len(),
+                "players_count": players.len(),
+                "returning_players_count": returning_players_count,
+                "average_duration": average_duration,
+                "first_game_at": games.first().map(|game| game.played_at),
+                "last_game_at": games.last().map(|game| game.played_at),
+                "$months": json!({
+                    "items": months.iter().map(|(month, count)| json!({
+                        "month": month,
+                        "games_count": count,
+                    })).collect::<Vec<_>>(),
+                    "count": months.len(),
+                }),
+                "$calendar": json!({
+                    "items": calendar.iter().map(|(date, count)| json!({
+                        "date": date,
+                        "games_count": count,
+                    })).collect::<Vec<_>>(),
+                    "count": calendar.len(),
+                }),
+                "$weekdays": json!({
+                    "items": weekdays.iter().enumerate().map(|(weekday, count)| json!({
+                        "weekday": weekday,
+                        "games_count": count,
+                    })).collect::<Vec<_>>(),
+                    "count": weekdays.len(),
+                }),
+                "$hours": json!({
+                    "items": hours.iter().enumerate().map(|(hour, count)| json!({
+                        "hour": hour,
+                        "games_count": count,
+                    })).collect::<Vec<_>>(),
+                    "count": hours.len(),
+                }),
+                "$top_players": json!({
+                    "items": top_players.iter().map(|(player_uuid, count)| json!({
+                        "player_uuid": player_uuid,
+                        "nickname": nicknames.get(player_uuid),
+                        "games_count": count,
+                    })).collect::<Vec<_>>(),
+                    "count": top_players.len(),
+                }),
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+async fn authorize(
+    conn: &mut sqlx::SqliteConnection,
+    place_uuid: &str,
+    current_user: &users::CurrentUser,
+    current_admin: &Option<users::CurrentAdmin>,
+) -> Result<(), AppError> {
+    let place = sqlx::query!(
+        "SELECT status, creator_uid FROM places WHERE uuid = ?",
+        place_uuid,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::PlaceNotFound)?;
+
+    let is_pending_own = place.status == PLACE_STATUS_PENDING
+        && place.creator_uid.as_deref() == Some(current_user.user_uid.as_str());
+
+    if current_admin.is_some() || is_pending_own {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden)
+    }
+}
+
+pub async fn places_show(
+    conn: &mut sqlx::SqliteConnection,
+    place_uuid: &str,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let place = sqlx::query!(
+        r#"SELECT
+            uuid, name, street, city, country_code, type as "place_type!", latitude, longitude,
+            status, rejection_reason, created_at, updated_at, moderated_at, archived_at
+        FROM places WHERE uuid = ?"#,
+        place_uuid,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::PlaceNotFound)?;
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "uuid": place.uuid,
+                "name": place.name,
+                "street": place.street,
+                "city": place.city,
+                "country_code": place.country_code,
+                "type": place.place_type,
+                "latitude": place.latitude,
+                "longitude": place.longitude,
+                "status": place.status,
+                "rejection_reason": place.rejection_reason,
+                "created_at": place.created_at,
+                "updated_at": place.updated_at,
+                "moderated_at": place.moderated_at,
+                "archived_at": place.archived_at,
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+fn is_name_conflict(err: &sqlx::Error) -> bool {
+    // sqlite reports violation of places_name_uidx by the indexed column
+    err.as_database_error()
+        .is_some_and(|err| err.message().contains("UNIQUE constraint failed: places.name"))
+}
+
+/// Turns violation of places_name_uidx into conflict pointing to the place using the name,
+/// only pending and approved places not archived hold their names
+pub async fn name_conflict(conn: &mut sqlx::SqliteConnection, name: &str, err: sqlx::Error) -> AppError {
+    if !is_name_conflict(&err) {
+        return err.into();
+    }
+
+    match sqlx::query_scalar!(
+        "SELECT uuid FROM places WHERE name = ? AND status != ? AND archived_at IS NULL",
+        name,
+        PLACE_STATUS_REJECTED,
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    {
+        Ok(Some(place_uuid)) => AppError::PlaceNameConflict(place_uuid),
+        Ok(None) => err.into(),
+        Err(err) => err.into(),
+    }
+}
+
+// games older than the window don't count, the weight of a game halves every half-life
+const RANK_WINDOW: i64 = 90 * 24 * 60 * 60;
+const RANK_HALF_LIFE: f64 = 30.0 * 24.0 * 60.0 * 60.0;
+// a distinct player counts as much as this many games
+const RANK_PLAYER_WEIGHT: f64 = 2.0;
+
+#[derive(Default)]
+struct PlaceActivity {
+    score: f64,
+    // last game of each player at the place
+    players: HashMap<String, i64>,
+}
+
+/// Ranks places by recent activity: games hosted, distinct players and recency of both.
+/// only ended games count, undone and hidden games are skipped like in `places_stats`.
+/// the most relevant place has rank 1, places without games in the window have rank -1
+pub async fn compute_places_rank(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp();
+    let since = now - RANK_WINDOW;
+    let weight = |at: i64| 0.5_f64.powf((now - at).max(0) as f64 / RANK_HALF_LIFE);
+
+    // game is dated by its start like in `places_stats`, not by creation of the session
+    let data = sqlx::query!(
+        r#"WITH games AS (
+            SELECT
+                gs.place_uuid, gs.player1_uuid, gs.player2_uuid, gs.player3_uuid, gs.player4_uuid,
+                COALESCE(MIN(CASE WHEN e.event_type = 'start' THEN e.created_at END), gs.created_at) as played_at
+            FROM game_sessions gs
+            INNER JOIN game_session_events e ON e.game_session_uuid = gs.uuid
+            WHERE gs.is_hidden = 0
+            GROUP BY gs.uuid
+            HAVING SUM(e.event_type = 'end') > 0 AND SUM(e.event_type = 'undo_game') = 0
+        )
+        SELECT
+            place_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid, played_at as "played_at!: i64"
+        FROM games
+        WHERE played_at >= ?"#,
+        since,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut activity = HashMap::<String, PlaceActivity>::new();
+
+    for row in data {
+        let place = activity.entry(row.place_uuid).or_default();
+        place.score += weight(row.played_at);
+
+        for player_uuid in [row.player1_uuid, row.player2_uuid, row.player3_uuid, row.player4_uuid] {
+            let last_at = place.players.entry(player_uuid).or_insert(row.played_at);
+            *last_at = (*last_at).max(row.played_at);
+        }
+    }
+
+    let mut ranked = activity
+        .into_iter()
+        .map(|(place_uuid, place)| {
+            let players_score = place.players.values().map(|&at| weight(at)).sum::<f64>();
+
+            (place_uuid, place.score + RANK_PLAYER_WEIGHT * players_score)
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(a_uuid, a), (b_uuid, b)| b.total_cmp(a).then_with(|| a_uuid.cmp(b_uuid)));
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!("UPDATE places SET rank = -1 WHERE rank != -1")
+        .execute(&mut tx)
+        .await?;
+
+    for (position, (place_uuid, _)) in ranked.iter().enumerate() {
+        let rank = position as i64 + 1;
+
+        sqlx::query!("UPDATE places SET rank = ? WHERE uuid = ?", rank, place_uuid)
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    info!("ranked {} places with games in last {} days", ranked.len(), RANK_WINDOW / 86400);
+
+    Ok(())
+}
@@ -1,15 +1,17 @@
 use axum::{handler::Handler, response::{IntoResponse, Response}, routing::{get, post}, Json, Router, async_trait, extract::{Path, FromRequest, RequestParts}};
+use hashbrown::HashMap;
 use hyper::StatusCode;
 use rand::prelude::SliceRandom;
 use serde::Deserialize;
 use serde_json::json;
 use tower_http::compression::CompressionLayer;
-use validator::Validate;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 use crate::{
     app::{AppError},
     db::DatabaseConnection,
-    firebase, users,
+    firebase, gdpr, geo, users, search,
+    scoring::{self, GameEvent, HandKind},
 };
 use crate::validate::ValidatedQuery;
 
@@ -19,37 +21,85 @@ pub fn router() -> Router {
         "/rankings/:ranking_uuid/players",
         get(players_index),
     )
+    .route(
+        "/rankings/:ranking_uuid/players/:player_uuid",
+        get(players_show),
+    )
+    .route(
+        "/rankings/:ranking_uuid/players/:player_uuid/versus/:opponent_uuid",
+        get(players_versus),
+    )
+}
+
+#[derive(Deserialize, Validate)]
+pub struct PlayersIndex {
+    // searched by prefix of any word of nickname, first / last name or usma_id,
+    // at least two chars so a single letter does not match half of the players
+    #[validate(length(min = 2, max = 32))]
+    q: Option<String>,
+    #[validate(custom = "geo::validate_country_code")]
+    country_code: Option<String>,
+    #[validate(length(min = 1, max = 64))]
+    region: Option<String>,
+    #[validate(custom = "geo::validate_region_code")]
+    region_code: Option<String>,
+    after: Option<i64>,
 }
 
 pub async fn players_index(
     _claims: firebase::FirebaseClaims,
-    _current_user: users::CurrentUser,
+    current_user: users::CurrentUser,
     Path(ranking_uuid): Path<String>,
+    ValidatedQuery(input): ValidatedQuery<PlayersIndex>,
     DatabaseConnection(conn): DatabaseConnection,
 ) -> Result<impl IntoResponse, AppError> {
+    const PAGE_LIMIT: i64 = 50;
     let mut conn = conn;
+    // search terms are folded, so `krakow` matches `Kraków` and the other way round
+    let pattern = input.q.as_deref().map(search::word_prefix_pattern);
+    let fetch_limit = PAGE_LIMIT + 1;
 
-    let data = sqlx::query!(
+    let mut data = sqlx::query!(
         r#"SELECT
-            uuid, usma_id, first_name, last_name, city, region, country_code,
-            nickname, "is_exam_done: bool" as is_exam_done,
-            "is_gdpr_agreed: bool" as is_gdpr_agreed,
-            "is_guest: bool" as is_guest, "is_static: bool" as is_static
-        FROM players_cache WHERE ranking_uuid = ? ORDER BY created_at DESC LIMIT 1000000"#,
+            rowid as "rowid!", uuid, usma_id, first_name, last_name, city, region, region_code, country_code,
+            nickname, is_exam_done as "is_exam_done: bool",
+            is_gdpr_agreed as "is_gdpr_agreed: bool",
+            is_guest as "is_guest: bool", is_static as "is_static: bool"
+        FROM players_cache
+        WHERE ranking_uuid = ?1 AND deleted_at IS NULL
+            AND (?2 IS NULL OR search_terms LIKE ?2 ESCAPE '\')
+            AND (?3 IS NULL OR country_code = ?3)
+            AND (?4 IS NULL OR region = ?4)
+            AND (?5 IS NULL OR region_code = ?5)
+            AND (?6 IS NULL OR rowid < ?6)
+        ORDER BY rowid DESC
+        LIMIT ?7"#,
         ranking_uuid,
+        pattern,
+        input.country_code,
+        input.region,
+        input.region_code,
+        input.after,
+        fetch_limit,
     )
     .fetch_all(&mut conn)
     .await?;
 
+    let has_more = data.len() as i64 > PAGE_LIMIT;
+    data.truncate(PAGE_LIMIT as usize);
+
     Ok(Json(json!({
         "items": data.iter().map(|row| {
+            let visible = gdpr::is_visible(row.is_gdpr_agreed, &row.uuid, &current_user);
+
             json!({
                 "uuid": row.uuid,
                 "usma_id": row.usma_id,
-                "first_name": row.first_name,
-                "last_name": row.last_name,
-                "city": row.city,
+                "first_name": gdpr::mask(visible, &row.first_name),
+                "last_name": gdpr::mask(visible, &row.last_name),
+                "city": gdpr::mask(visible, &row.city),
                 "region": row.region,
+                "region_code": row.region_code,
                 "country_code": row.country_code,
                 "nickname": row.nickname,
                 "is_exam_done": row.is_exam_done,
@@ -59,6 +109,257 @@ pub async fn players_index(
             })
         }).collect::<Vec<_>>(),
         "count": data.len(),
+        "cursor": if has_more { data.last().map(|row| row.rowid) } else { None },
     })))
 }
 
+fn rate(count: i64, total: i64) -> Option<f64> {
+    if total > 0 {
+        Some(count as f64 / total as f64)
+    } else {
+        None
+    }
+}
+
+pub async fn players_show(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    Path((ranking_uuid, player_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let player = sqlx::query!(
+        r#"SELECT
+            p.uuid, p.usma_id, p.ema_id, p.first_name, p.last_name, p.city, p.region, p.region_code, p.country_code,
+            p.nickname, p.is_exam_done as "is_exam_done: bool",
+            p.is_gdpr_agreed as "is_gdpr_agreed: bool",
+            p.is_guest as "is_guest: bool", p.is_static as "is_static: bool",
+            s.rank_uuid as "rank_uuid?", s.rank_points as "rank_points?", s.elo_points as "elo_points?",
+            s.rating_deviation, s.is_listed as "is_listed?: bool", s.unlisted_reason
+        FROM players_cache p
+        LEFT JOIN ranking_snapshot_cache s ON s.player_uuid = p.uuid AND s.ranking_uuid = p.ranking_uuid
+        WHERE p.ranking_uuid = ? AND p.uuid = ? AND p.deleted_at IS NULL"#,
+        ranking_uuid,
+        player_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::PlayerNotFound)?;
+
+    let stats = sqlx::query!(
+        "SELECT
+            games_count, placement1_count, placement2_count, placement3_count, placement4_count,
+            hands_count, wins_count, tsumo_count, ron_count, deal_in_count, riichi_count,
+            chonbo_count, average_hand_value, best_game_session_uuid, best_game_points,
+            worst_game_session_uuid, worst_game_points, created_at
+        FROM player_stats_cache
+        WHERE ranking_uuid = ? AND player_uuid = ?",
+        ranking_uuid,
+        player_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?;
+
+    let visible = gdpr::is_visible(player.is_gdpr_agreed, &player.uuid, &current_user);
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "uuid": player.uuid,
+                "usma_id": player.usma_id,
+                "ema_id": player.ema_id,
+                "first_name": gdpr::mask(visible, &player.first_name),
+                "last_name": gdpr::mask(visible, &player.last_name),
+                "city": gdpr::mask(visible, &player.city),
+                "region": player.region,
+                "region_code": player.region_code,
+                "country_code": player.country_code,
+                "nickname": player.nickname,
+                "is_exam_done": player.is_exam_done,
+                "is_gdpr_agreed": player.is_gdpr_agreed,
+                "is_guest": player.is_guest,
+                "is_static": player.is_static,
+                "rank_uuid": player.rank_uuid,
+                "rank_points": player.rank_points,
+                "elo_points": player.elo_points,
+                "rating_deviation": player.rating_deviation,
+                "is_listed": player.is_listed,
+                "unlisted_reason": player.unlisted_reason,
+                "$stats": stats.map(|stats| {
+                    let placements = [
+                        stats.placement1_count,
+                        stats.placement2_count,
+                        stats.placement3_count,
+                        stats.placement4_count,
+                    ];
+                    let placement_sum = placements
+                        .iter()
+                        .enumerate()
+                        .map(|(i, count)| (i as i64 + 1) * count)
+                        .sum::<i64>();
+
+                    json!({
+                        "games_count": stats.games_count,
+                        "placements": placements,
+                        "average_placement": rate(placement_sum, stats.games_count),
+                        "hands_count": stats.hands_count,
+                        "win_rate": rate(stats.wins_count, stats.hands_count),
+                        "deal_in_rate": rate(stats.deal_in_count, stats.hands_count),
+                        "riichi_rate": rate(stats.riichi_count, stats.hands_count),
+                        "tsumo_count": stats.tsumo_count,
+                        "ron_count": stats.ron_count,
+                        "average_hand_value": stats.average_hand_value,
+                        "chonbo_count": stats.chonbo_count,
+                        "best_game": stats.best_game_session_uuid.map(|uuid| json!({
+                            "game_session_uuid": uuid,
+                            "points": stats.best_game_points,
+                        })),
+                        "worst_game": stats.worst_game_session_uuid.map(|uuid| json!({
+                            "game_session_uuid": uuid,
+                            "points": stats.worst_game_points,
+                        })),
+                        "computed_at": stats.created_at,
+                    })
+                }),
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+/// Head-to-head record of two players over their shared, computed game sessions
+pub async fn players_versus(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path((ranking_uuid, player_uuid, opponent_uuid)): Path<(String, String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    if player_uuid == opponent_uuid {
+        let mut errors = ValidationErrors::new();
+        errors.add("opponent_uuid", ValidationError::new("player can't be own opponent"));
+
+        return Err(AppError::ValidationError(errors));
+    }
+
+    for uuid in [&player_uuid, &opponent_uuid] {
+        sqlx::query_scalar!(
+            "SELECT 1 FROM players_cache WHERE ranking_uuid = ? AND uuid = ?",
+            ranking_uuid,
+            uuid,
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        .ok_or(AppError::PlayerNotFound)?;
+    }
+
+    let games = sqlx::query!(
+        r#"SELECT
+            gs.uuid, gs.player1_uuid, gs.player2_uuid, gs.player3_uuid, gs.player4_uuid,
+            a.placement, a.points, a.rating_before, a.rating_after, a.ended_at,
+            b.placement as opponent_placement, b.points as opponent_points,
+            b.rating_before as opponent_rating_before, b.rating_after as opponent_rating_after
+        FROM game_sessions gs
+        INNER JOIN game_session_results_cache a ON a.game_session_uuid = gs.uuid AND a.player_uuid = ?1
+        INNER JOIN game_session_results_cache b ON b.game_session_uuid = gs.uuid AND b.player_uuid = ?2
+        WHERE gs.ranking_uuid = ?3
+        AND ?1 IN (gs.player1_uuid, gs.player2_uuid, gs.player3_uuid, gs.player4_uuid)
+        AND ?2 IN (gs.player1_uuid, gs.player2_uuid, gs.player3_uuid, gs.player4_uuid)
+        ORDER BY a.ended_at DESC"#,
+        player_uuid,
+        opponent_uuid,
+        ranking_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let rows = sqlx::query!(
+        "SELECT e.game_session_uuid, e.event_type, e.event_data, e.created_at
+        FROM game_session_events e
+        INNER JOIN game_sessions gs ON gs.uuid = e.game_session_uuid
+        WHERE gs.ranking_uuid = ?3
+        AND ?1 IN (gs.player1_uuid, gs.player2_uuid, gs.player3_uuid, gs.player4_uuid)
+        AND ?2 IN (gs.player1_uuid, gs.player2_uuid, gs.player3_uuid, gs.player4_uuid)
+        ORDER BY e.created_at ASC, e.rowid ASC",
+        player_uuid,
+        opponent_uuid,
+        ranking_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let mut events: HashMap<String, Vec<GameEvent>> = HashMap::new();
+    for row in rows {
+        events.entry(row.game_session_uuid).or_default().push(GameEvent {
+            event_type: row.event_type,
+            event_data: row.event_data,
+            created_at: row.created_at,
+        });
+    }
+
+    // points paid through ron: (player from opponent, opponent from player)
+    let mut ron_points = (0, 0);
+    for game in &games {
+        let players = [
+            game.player1_uuid.clone(),
+            game.player2_uuid.clone(),
+            game.player3_uuid.clone(),
+            game.player4_uuid.clone(),
+        ];
+        let seat = players.iter().position(|uuid| *uuid == player_uuid);
+        let opponent_seat = players.iter().position(|uuid| *uuid == opponent_uuid);
+        let session_events = events.get(&game.uuid).map_or(&[][..], |events| events.as_slice());
+
+        let outcome = match scoring::replay(&players, session_events) {
+            Ok(Some(outcome)) => outcome,
+            _ => continue,
+        };
+
+        for hand in outcome.hands.iter().filter(|hand| hand.kind == HandKind::Ron) {
+            for win in &hand.wins {
+                if Some(win.seat) == seat && hand.loser == opponent_seat {
+                    ron_points.0 += win.value;
+                } else if Some(win.seat) == opponent_seat && hand.loser == seat {
+                    ron_points.1 += win.value;
+                }
+            }
+        }
+    }
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "player_uuid": player_uuid,
+                "opponent_uuid": opponent_uuid,
+                "games_count": games.len(),
+                "placed_above_count": games.iter().filter(|game| game.placement < game.opponent_placement).count(),
+                "placed_below_count": games.iter().filter(|game| game.placement > game.opponent_placement).count(),
+                "ron_points_won": ron_points.0,
+                "ron_points_lost": ron_points.1,
+                "rating_change": games.iter().map(|game| game.rating_after - game.rating_before).sum::<f64>(),
+                "opponent_rating_change": games
+                    .iter()
+                    .map(|game| game.opponent_rating_after - game.opponent_rating_before)
+                    .sum::<f64>(),
+                "$games": json!({
+                    "items": games.iter().map(|game| {
+                        json!({
+                            "game_session_uuid": game.uuid,
+                            "ended_at": game.ended_at,
+                            "placement": game.placement,
+                            "points": game.points,
+                            "rating_change": game.rating_after - game.rating_before,
+                            "opponent_placement": game.opponent_placement,
+                            "opponent_points": game.opponent_points,
+                            "opponent_rating_change": game.opponent_rating_after - game.opponent_rating_before,
+                        })
+                    }).collect::<Vec<_>>(),
+                    "count": games.len(),
+                }),
+            })
+        ],
+        "count": 1,
+    })))
+}
@@ -4,14 +4,15 @@ use rand::prelude::SliceRandom;
 use serde::Deserialize;
 use serde_json::json;
 use tower_http::compression::CompressionLayer;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 use crate::{
     app::{AppError},
+    compute::{self, RankingRules},
     db::DatabaseConnection,
-    firebase, users,
+    firebase, gdpr, geo, rating, users,
 };
-use crate::validate::ValidatedQuery;
+use crate::validate::{ValidatedJson, ValidatedQuery};
 
 pub fn router() -> Router {
     Router::new()
@@ -23,6 +24,14 @@ pub fn router() -> Router {
             "/rankings/:ranking_uuid/list",
             get(rankings_list),
         )
+        .route(
+            "/rankings/:ranking_uuid/leaderboard",
+            get(rankings_leaderboard),
+        )
+        .route(
+            "/rankings/:ranking_uuid/preview",
+            post(rankings_preview),
+        )
 }
 
 pub async fn rankings_index(
@@ -34,8 +43,10 @@ pub async fn rankings_index(
 
     let data = sqlx::query!(
         r#"SELECT
-            uuid, name, created_at, archived_at
-        FROM rankings_cache ORDER BY created_at DESC, archived_at DESC NULLS LAST"#
+            uuid, name, rating_algorithm, min_games, min_opponents, inactivity_days,
+            inactivity_policy, is_guest_listed as "is_guest_listed: bool",
+            created_at, archived_at, computed_at
+        FROM rankings_cache WHERE deleted_at IS NULL ORDER BY created_at DESC, archived_at DESC NULLS LAST"#
     )
         .fetch_all(&mut conn)
         .await?;
@@ -45,8 +56,15 @@ pub async fn rankings_index(
             json!({
                 "uuid": row.uuid,
                 "name": row.name,
+                "rating_algorithm": row.rating_algorithm,
+                "min_games": row.min_games,
+                "min_opponents": row.min_opponents,
+                "inactivity_days": row.inactivity_days,
+                "inactivity_policy": row.inactivity_policy,
+                "is_guest_listed": row.is_guest_listed,
                 "archived_at": row.archived_at,
                 "created_at": row.created_at,
+                "computed_at": row.computed_at,
             })
         }).collect::<Vec<_>>(),
         "count": data.len(),
@@ -63,7 +81,8 @@ pub async fn rankings_list(
 
     let data = sqlx::query!(
         r#"SELECT
-            player_uuid, rank_uuid, rank_points, elo_points
+            player_uuid, rank_uuid, rank_points, elo_points, rating_deviation, rating_volatility,
+            games_count, is_listed as "is_listed: bool", unlisted_reason
         FROM ranking_snapshot_cache WHERE ranking_uuid = ?"#,
         ranking_uuid,
     )
@@ -77,9 +96,285 @@ pub async fn rankings_list(
                 "rank_uuid": row.rank_uuid,
                 "rank_points": row.rank_points,
                 "elo_points": row.elo_points,
+                "rating_deviation": row.rating_deviation,
+                "rating_volatility": row.rating_volatility,
+                "games_count": row.games_count,
+                "is_listed": row.is_listed,
+                "unlisted_reason": row.unlisted_reason,
             })
         }).collect::<Vec<_>>(),
         "count": data.len(),
     })))
 }
 
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardSort {
+    EloPoints,
+    RankPoints,
+}
+
+impl LeaderboardSort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LeaderboardSort::EloPoints => "elo_points",
+            LeaderboardSort::RankPoints => "rank_points",
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct RankingsLeaderboard {
+    sort: Option<LeaderboardSort>,
+    #[validate(custom = "geo::validate_country_code")]
+    country_code: Option<String>,
+    #[validate(length(min = 1, max = 64))]
+    region: Option<String>,
+    #[validate(custom = "geo::validate_region_code")]
+    region_code: Option<String>,
+    #[validate(length(min = 1, max = 64))]
+    city: Option<String>,
+    #[validate(length(equal = 36))]
+    rank_uuid: Option<String>,
+    is_guest: Option<bool>,
+    // show players not meeting ranking qualification rules as well
+    include_unlisted: Option<bool>,
+    after: Option<i64>,
+}
+
+pub async fn rankings_leaderboard(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    ValidatedQuery(input): ValidatedQuery<RankingsLeaderboard>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    const PAGE_LIMIT: i64 = 20;
+    let mut conn = conn;
+    let sort = input.sort.unwrap_or(LeaderboardSort::EloPoints).as_str();
+    let cursor = input.after.unwrap_or(0).max(0);
+    let last_position = cursor + PAGE_LIMIT + 1;
+    let include_unlisted = input.include_unlisted.unwrap_or(false);
+
+    // position is computed over filtered rows, so it's the position within national,
+    // regional, etc. leaderboard. requesting player row is always fetched,
+    // unlisted player has no position
+    let data = sqlx::query!(
+        r#"WITH leaderboard AS (
+            SELECT
+                s.player_uuid, s.rank_uuid, s.rank_points, s.elo_points,
+                s.rating_deviation, s.rating_volatility,
+                p.nickname, p.first_name, p.last_name, p.city, p.region, p.region_code, p.country_code,
+                p.is_guest as "is_guest: bool", p.is_gdpr_agreed as "is_gdpr_agreed: bool",
+                s.games_count, s.is_listed as "is_listed: bool", s.unlisted_reason,
+                r.name as rank_name, r.color as rank_color,
+                CASE WHEN s.is_listed = 1 OR ?11 THEN ROW_NUMBER() OVER (
+                    PARTITION BY s.is_listed = 1 OR ?11
+                    ORDER BY CASE WHEN ?1 = 'rank_points' THEN s.rank_points ELSE s.elo_points END DESC,
+                    s.player_uuid ASC
+                ) END as position
+            FROM ranking_snapshot_cache s
+            INNER JOIN players_cache p ON p.uuid = s.player_uuid
+            INNER JOIN ranks_cache r ON r.uuid = s.rank_uuid
+            WHERE s.ranking_uuid = ?2
+            AND (?3 IS NULL OR p.country_code = ?3)
+            AND (?4 IS NULL OR p.region = ?4)
+            AND (?12 IS NULL OR p.region_code = ?12)
+            AND (?5 IS NULL OR (p.city = ?5 AND (p.is_gdpr_agreed = 1 OR p.uuid = ?10)))
+            AND (?6 IS NULL OR s.rank_uuid = ?6)
+            AND (?7 IS NULL OR p.is_guest = ?7)
+            AND (s.is_listed = 1 OR ?11 OR s.player_uuid = ?10)
+        )
+        SELECT
+            player_uuid, rank_uuid, rank_points, elo_points, rating_deviation, rating_volatility,
+            nickname, first_name, last_name, city, region, region_code, country_code, "is_guest: bool", "is_gdpr_agreed: bool",
+            games_count, "is_listed: bool", unlisted_reason,
+            rank_name, rank_color, position as "position: i64"
+        FROM leaderboard
+        WHERE (position > ?8 AND position <= ?9) OR player_uuid = ?10
+        ORDER BY position ASC"#,
+        sort,
+        ranking_uuid,
+        input.country_code,
+        input.region,
+        input.city,
+        input.rank_uuid,
+        input.is_guest,
+        cursor,
+        last_position,
+        current_user.player_uuid,
+        include_unlisted,
+        input.region_code,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let items = data
+        .iter()
+        .map(|row| {
+            let visible = gdpr::is_visible(row.is_gdpr_agreed, &row.player_uuid, &current_user);
+
+            (row.position, row.player_uuid == current_user.player_uuid, json!({
+                "position": row.position,
+                "player_uuid": row.player_uuid,
+                "rank_uuid": row.rank_uuid,
+                "rank_points": row.rank_points,
+                "elo_points": row.elo_points,
+                "rating_deviation": row.rating_deviation,
+                "rating_volatility": row.rating_volatility,
+                "games_count": row.games_count,
+                "is_listed": row.is_listed,
+                "unlisted_reason": row.unlisted_reason,
+                "$player": json!({
+                    "uuid": row.player_uuid,
+                    "nickname": row.nickname,
+                    "first_name": gdpr::mask(visible, &row.first_name),
+                    "last_name": gdpr::mask(visible, &row.last_name),
+                    "city": gdpr::mask(visible, &row.city),
+                    "region": row.region,
+                    "region_code": row.region_code,
+                    "country_code": row.country_code,
+                    "is_guest": row.is_guest,
+                }),
+                "$rank": json!({
+                    "uuid": row.rank_uuid,
+                    "name": row.rank_name,
+                    "color": row.rank_color,
+                }),
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    let page = items
+        .iter()
+        .filter(|(position, _, _)| position.is_some_and(|position| position > cursor && position <= cursor + PAGE_LIMIT))
+        .map(|(_, _, item)| item)
+        .collect::<Vec<_>>();
+    let has_more = items.iter().any(|(position, _, _)| *position == Some(last_position));
+
+    Ok(Json(json!({
+        "count": page.len(),
+        "items": page,
+        "cursor": if has_more { Some(cursor + PAGE_LIMIT) } else { None },
+        "$me": items.iter().find(|(_, is_me, _)| *is_me).map(|(_, _, item)| item),
+    })))
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_rankings_preview_input"))]
+pub struct RankingsPreview {
+    #[validate(length(equal = 4))]
+    players_uuids: Vec<String>,
+    #[serde(default)]
+    is_novice_friendly: bool,
+    #[serde(default)]
+    is_unranked: bool,
+}
+
+/// What is at stake before the game starts - probability of every finishing order
+/// and rating and rank points change it would bring, computed same way as ranking is
+pub async fn rankings_preview(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    ValidatedJson(input): ValidatedJson<RankingsPreview>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    sqlx::query_scalar!(
+        "SELECT 1 FROM rankings_cache WHERE uuid = ? AND deleted_at IS NULL",
+        ranking_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::RankingNotFound)?;
+
+    let found = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM players_cache
+        WHERE ranking_uuid = ? AND uuid IN (?, ?, ?, ?)"#,
+        ranking_uuid,
+        input.players_uuids[0],
+        input.players_uuids[1],
+        input.players_uuids[2],
+        input.players_uuids[3],
+    )
+    .fetch_one(&mut conn)
+    .await?;
+
+    if found != 4 {
+        return Err(AppError::PlayerNotFound);
+    }
+
+    let rules = RankingRules::fetch(&mut conn, &ranking_uuid)
+        .await
+        .map_err(|err| AppError::Unknown(Some(err.into())))?;
+    let algorithm = rules.algorithm.algorithm();
+    let current = compute::current_ratings(&mut conn, algorithm.as_ref(), &ranking_uuid, &input.players_uuids).await?;
+    let ratings = current.iter().map(|(rating, _)| *rating).collect::<Vec<_>>();
+    let games_played = current.iter().map(|(_, games)| *games).collect::<Vec<_>>();
+
+    let outcomes = rating::finishing_orders(algorithm.as_ref(), &ratings)
+        .into_iter()
+        .map(|(placements, probability)| {
+            let results = rules.rate_game(
+                algorithm.as_ref(),
+                &ratings,
+                &games_played,
+                &placements,
+                input.is_unranked,
+                input.is_novice_friendly,
+            );
+
+            (placements, probability, results)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(json!({
+        "items": input.players_uuids.iter().enumerate().map(|(i, player_uuid)| {
+            json!({
+                "player_uuid": player_uuid,
+                "rating": ratings[i].value,
+                "rating_deviation": ratings[i].deviation,
+                "placement_probabilities": (1..=4u8).map(|placement| {
+                    outcomes
+                        .iter()
+                        .filter(|(placements, _, _)| placements[i] == placement)
+                        .map(|(_, probability, _)| probability)
+                        .sum::<f64>()
+                }).collect::<Vec<_>>(),
+            })
+        }).collect::<Vec<_>>(),
+        "count": input.players_uuids.len(),
+        "$outcomes": json!({
+            "items": outcomes.iter().map(|(placements, probability, results)| {
+                json!({
+                    "placements": placements,
+                    "probability": probability,
+                    "results": input.players_uuids.iter().zip(results.iter()).map(|(player_uuid, result)| {
+                        json!({
+                            "player_uuid": player_uuid,
+                            "rating_change": result.rating_after.value - result.rating_before.value,
+                            "rank_points": result.rank_points,
+                            "weighting": result.weighting.as_str(),
+                        })
+                    }).collect::<Vec<_>>(),
+                })
+            }).collect::<Vec<_>>(),
+            "count": outcomes.len(),
+        }),
+    })))
+}
+
+fn validate_rankings_preview_input(input: &RankingsPreview) -> Result<(), ValidationError> {
+    let mut players_uuids = input.players_uuids.iter().collect::<Vec<_>>();
+    players_uuids.sort();
+    players_uuids.dedup();
+
+    if players_uuids.len() != input.players_uuids.len() {
+        Err(ValidationError::new("players must be distinct"))
+    } else {
+        Ok(())
+    }
+}
@@ -32,7 +32,7 @@ pub async fn ranks_index(
     let data = sqlx::query!(
         r#"SELECT
             uuid, name, required_points, required_exam, color
-        FROM ranks_cache ORDER BY created_at ASC"#
+        FROM ranks_cache WHERE deleted_at IS NULL ORDER BY created_at ASC"#
     )
     .fetch_all(&mut conn)
     .await?;
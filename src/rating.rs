@@ -0,0 +1,518 @@
+use std::{f64::consts::PI, str::FromStr};
+
+/// Rating of a single player as seen by a rating algorithm.
+/// `deviation` and `volatility` are only provided by algorithms that track uncertainty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rating {
+    pub value: f64,
+    pub deviation: Option<f64>,
+    pub volatility: Option<f64>,
+}
+
+impl Rating {
+    /// Rating change from `self` to `after` scaled by `weight`
+    pub fn weighted(&self, after: &Rating, weight: f64) -> Rating {
+        let scale = |before: Option<f64>, after: Option<f64>| match (before, after) {
+            (Some(before), Some(after)) => Some(before + (after - before) * weight),
+            (_, after) => after,
+        };
+
+        Rating {
+            value: self.value + (after.value - self.value) * weight,
+            deviation: scale(self.deviation, after.deviation),
+            volatility: scale(self.volatility, after.volatility),
+        }
+    }
+}
+
+pub trait RatingAlgorithm: Send + Sync {
+    fn initial(&self) -> Rating;
+
+    /// `placements` are 1-based and may contain ties, i.e. `[1, 2, 2, 4]`
+    fn rate(&self, ratings: &[Rating], placements: &[u8]) -> Vec<Rating>;
+
+    /// Strength of the player in Plackett-Luce model,
+    /// by default consistent with elo expected score
+    fn strength(&self, rating: &Rating) -> f64 {
+        10f64.powf(rating.value / 400.0)
+    }
+}
+
+/// Probability of every finishing order of the players, in Plackett-Luce model
+/// with strengths given by rating algorithm. Placements are in order of `ratings`
+pub fn finishing_orders(algorithm: &dyn RatingAlgorithm, ratings: &[Rating]) -> Vec<(Vec<u8>, f64)> {
+    let strengths = ratings.iter().map(|rating| algorithm.strength(rating)).collect::<Vec<_>>();
+    let mut orders = Vec::new();
+    let mut order = (0..ratings.len()).collect::<Vec<_>>();
+
+    permute(&mut order, 0, &mut |order| {
+        let mut probability = 1.0;
+        let mut remaining = strengths.iter().sum::<f64>();
+        let mut placements = vec![0u8; order.len()];
+
+        for (place, &i) in order.iter().enumerate() {
+            probability *= strengths[i] / remaining;
+            remaining -= strengths[i];
+            placements[i] = place as u8 + 1;
+        }
+
+        orders.push((placements, probability));
+    });
+
+    orders
+}
+
+fn permute(items: &mut Vec<usize>, k: usize, f: &mut impl FnMut(&[usize])) {
+    if k == items.len() {
+        f(items);
+        return;
+    }
+
+    for i in k..items.len() {
+        items.swap(k, i);
+        permute(items, k + 1, f);
+        items.swap(k, i);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RatingAlgorithmKind {
+    Elo,
+    Glicko2,
+    OpenSkill,
+}
+
+impl RatingAlgorithmKind {
+    pub fn algorithm(&self) -> Box<dyn RatingAlgorithm> {
+        match self {
+            RatingAlgorithmKind::Elo => Box::new(Elo::default()),
+            RatingAlgorithmKind::Glicko2 => Box::new(Glicko2::default()),
+            RatingAlgorithmKind::OpenSkill => Box::new(OpenSkill::default()),
+        }
+    }
+}
+
+impl FromStr for RatingAlgorithmKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "elo" => Ok(RatingAlgorithmKind::Elo),
+            "glicko2" => Ok(RatingAlgorithmKind::Glicko2),
+            "openskill" => Ok(RatingAlgorithmKind::OpenSkill),
+            _ => Err(anyhow::Error::msg(format!("unknown rating algorithm: {}", s))),
+        }
+    }
+}
+
+/// Score of player `a` against player `b` derived from their placements
+fn pairwise_score(a: u8, b: u8) -> f64 {
+    match a.cmp(&b) {
+        std::cmp::Ordering::Less => 1.0,
+        std::cmp::Ordering::Equal => 0.5,
+        std::cmp::Ordering::Greater => 0.0,
+    }
+}
+
+/// Multiplayer elo - game is treated as a set of pairwise duels,
+/// k factor is split between opponents so a single game weighs the same as in 1v1
+pub struct Elo {
+    pub initial: f64,
+    pub k: f64,
+}
+
+impl Default for Elo {
+    fn default() -> Self {
+        Self {
+            initial: 1500.0,
+            k: 32.0,
+        }
+    }
+}
+
+impl RatingAlgorithm for Elo {
+    fn initial(&self) -> Rating {
+        Rating {
+            value: self.initial,
+            deviation: None,
+            volatility: None,
+        }
+    }
+
+    fn rate(&self, ratings: &[Rating], placements: &[u8]) -> Vec<Rating> {
+        let opponents = (ratings.len() - 1).max(1) as f64;
+
+        ratings
+            .iter()
+            .enumerate()
+            .map(|(i, rating)| {
+                let delta: f64 = ratings
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(j, other)| {
+                        let expected = 1.0 / (1.0 + 10f64.powf((other.value - rating.value) / 400.0));
+
+                        pairwise_score(placements[i], placements[j]) - expected
+                    })
+                    .sum();
+
+                Rating {
+                    value: rating.value + self.k / opponents * delta,
+                    ..*rating
+                }
+            })
+            .collect()
+    }
+}
+
+/// Glicko-2, each game is a separate rating period in which
+/// player faced every other player at the table
+/// see http://www.glicko.net/glicko/glicko2.pdf
+pub struct Glicko2 {
+    pub initial: f64,
+    pub initial_deviation: f64,
+    pub initial_volatility: f64,
+    pub tau: f64,
+}
+
+impl Default for Glicko2 {
+    fn default() -> Self {
+        Self {
+            initial: 1500.0,
+            initial_deviation: 350.0,
+            initial_volatility: 0.06,
+            tau: 0.5,
+        }
+    }
+}
+
+impl Glicko2 {
+    const SCALE: f64 = 173.7178;
+    const EPSILON: f64 = 0.000001;
+
+    fn g(phi: f64) -> f64 {
+        1.0 / (1.0 + 3.0 * phi.powi(2) / PI.powi(2)).sqrt()
+    }
+
+    fn volatility(&self, phi: f64, sigma: f64, delta: f64, v: f64) -> f64 {
+        let a = (sigma.powi(2)).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+
+            ex * (delta.powi(2) - phi.powi(2) - v - ex) / (2.0 * (phi.powi(2) + v + ex).powi(2))
+                - (x - a) / self.tau.powi(2)
+        };
+
+        let mut big_a = a;
+        let mut big_b = if delta.powi(2) > phi.powi(2) + v {
+            (delta.powi(2) - phi.powi(2) - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * self.tau) < 0.0 {
+                k += 1.0;
+            }
+
+            a - k * self.tau
+        };
+
+        let mut f_a = f(big_a);
+        let mut f_b = f(big_b);
+
+        while (big_b - big_a).abs() > Self::EPSILON {
+            let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+            let f_c = f(big_c);
+
+            if f_c * f_b <= 0.0 {
+                big_a = big_b;
+                f_a = f_b;
+            } else {
+                f_a /= 2.0;
+            }
+
+            big_b = big_c;
+            f_b = f_c;
+        }
+
+        (big_a / 2.0).exp()
+    }
+}
+
+impl RatingAlgorithm for Glicko2 {
+    fn initial(&self) -> Rating {
+        Rating {
+            value: self.initial,
+            deviation: Some(self.initial_deviation),
+            volatility: Some(self.initial_volatility),
+        }
+    }
+
+    fn rate(&self, ratings: &[Rating], placements: &[u8]) -> Vec<Rating> {
+        let scaled = ratings
+            .iter()
+            .map(|rating| {
+                (
+                    (rating.value - self.initial) / Self::SCALE,
+                    rating.deviation.unwrap_or(self.initial_deviation) / Self::SCALE,
+                    rating.volatility.unwrap_or(self.initial_volatility),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        scaled
+            .iter()
+            .enumerate()
+            .map(|(i, &(mu, phi, sigma))| {
+                let (v_inv, delta_sum) = scaled
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .fold((0.0, 0.0), |(v_inv, delta_sum), (j, &(mu_j, phi_j, _))| {
+                        let g = Self::g(phi_j);
+                        let e = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+
+                        (
+                            v_inv + g.powi(2) * e * (1.0 - e),
+                            delta_sum + g * (pairwise_score(placements[i], placements[j]) - e),
+                        )
+                    });
+                let v = 1.0 / v_inv;
+                let delta = v * delta_sum;
+
+                let sigma = self.volatility(phi, sigma, delta, v);
+                let phi_star = (phi.powi(2) + sigma.powi(2)).sqrt();
+                let phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / v).sqrt();
+                let mu = mu + phi.powi(2) * delta_sum;
+
+                Rating {
+                    value: mu * Self::SCALE + self.initial,
+                    deviation: Some(phi * Self::SCALE),
+                    volatility: Some(sigma),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Weng-Lin bayesian approximation with Plackett-Luce model (the OpenSkill default),
+/// parameters are the OpenSkill ones scaled by 60 so ratings are comparable with elo
+/// see https://jmlr.org/papers/v12/weng11a.html
+pub struct OpenSkill {
+    pub mu: f64,
+    pub sigma: f64,
+    pub beta: f64,
+    pub tau: f64,
+    pub kappa: f64,
+}
+
+impl Default for OpenSkill {
+    fn default() -> Self {
+        Self {
+            mu: 1500.0,
+            sigma: 500.0,
+            beta: 250.0,
+            tau: 5.0,
+            kappa: 0.0001,
+        }
+    }
+}
+
+impl RatingAlgorithm for OpenSkill {
+    fn initial(&self) -> Rating {
+        Rating {
+            value: self.mu,
+            deviation: Some(self.sigma),
+            volatility: None,
+        }
+    }
+
+    fn rate(&self, ratings: &[Rating], placements: &[u8]) -> Vec<Rating> {
+        let players = ratings
+            .iter()
+            .map(|rating| {
+                let sigma = rating.deviation.unwrap_or(self.sigma);
+
+                (rating.value, (sigma.powi(2) + self.tau.powi(2)).sqrt())
+            })
+            .collect::<Vec<_>>();
+
+        let c = players
+            .iter()
+            .map(|(_, sigma)| sigma.powi(2) + self.beta.powi(2))
+            .sum::<f64>()
+            .sqrt();
+        // sum of exp(mu / c) of players placed same or worse than q
+        let sums = placements
+            .iter()
+            .map(|&rank_q| {
+                players
+                    .iter()
+                    .zip(placements)
+                    .filter(|(_, &rank)| rank >= rank_q)
+                    .map(|((mu, _), _)| (mu / c).exp())
+                    .sum::<f64>()
+            })
+            .collect::<Vec<_>>();
+        // count of players sharing placement with q
+        let ties = placements
+            .iter()
+            .map(|&rank_q| placements.iter().filter(|&&rank| rank == rank_q).count() as f64)
+            .collect::<Vec<_>>();
+
+        players
+            .iter()
+            .enumerate()
+            .map(|(i, &(mu, sigma))| {
+                let exp_mu = (mu / c).exp();
+                let (omega, delta) = (0..players.len())
+                    .filter(|&q| placements[q] <= placements[i])
+                    .fold((0.0, 0.0), |(omega, delta), q| {
+                        let quotient = exp_mu / sums[q];
+                        let mu_factor = if q == i { 1.0 - quotient } else { -quotient };
+
+                        (
+                            omega + mu_factor / ties[q],
+                            delta + quotient * (1.0 - quotient) / ties[q],
+                        )
+                    });
+                let gamma = sigma / c;
+                let omega = omega * sigma.powi(2) / c;
+                let delta = delta * gamma * sigma.powi(2) / c.powi(2);
+
+                Rating {
+                    value: mu + omega,
+                    deviation: Some(sigma * (1.0 - delta).max(self.kappa).sqrt()),
+                    volatility: None,
+                }
+            })
+            .collect()
+    }
+
+    /// exp(mu / c) as in the update, with c of a table of four default players
+    fn strength(&self, rating: &Rating) -> f64 {
+        let c = (4.0 * (self.sigma.powi(2) + self.beta.powi(2))).sqrt();
+
+        (rating.value / c).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} to be within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    fn rating(value: f64, deviation: f64, volatility: f64) -> Rating {
+        Rating {
+            value,
+            deviation: Some(deviation),
+            volatility: Some(volatility),
+        }
+    }
+
+    #[test]
+    fn elo_symmetric_duel() {
+        let elo = Elo::default();
+        let ratings = elo.rate(&[elo.initial(), elo.initial()], &[1, 2]);
+
+        assert_close(ratings[0].value, 1516.0, 1e-9);
+        assert_close(ratings[1].value, 1484.0, 1e-9);
+    }
+
+    #[test]
+    fn elo_splits_k_between_opponents() {
+        let elo = Elo::default();
+        let ratings = elo.rate(&[elo.initial(); 4], &[1, 2, 3, 4]);
+
+        assert_close(ratings[0].value, 1516.0, 1e-9);
+        assert_close(ratings[1].value, 1500.0 + 16.0 / 3.0, 1e-9);
+        assert_close(ratings[2].value, 1500.0 - 16.0 / 3.0, 1e-9);
+        assert_close(ratings[3].value, 1484.0, 1e-9);
+    }
+
+    #[test]
+    fn elo_tie_between_equals_keeps_ratings() {
+        let elo = Elo::default();
+        let ratings = elo.rate(&[elo.initial(); 4], &[1, 1, 1, 1]);
+
+        assert!(ratings.iter().all(|rating| rating.value == 1500.0));
+    }
+
+    /// Worked example from section "Example calculation" of the Glicko-2 paper,
+    /// player beats the 1400 player and loses to the 1550 and 1700 ones
+    #[test]
+    fn glicko2_paper_example() {
+        let glicko2 = Glicko2::default();
+        let ratings = [
+            rating(1500.0, 200.0, 0.06),
+            rating(1400.0, 30.0, 0.06),
+            rating(1550.0, 100.0, 0.06),
+            rating(1700.0, 300.0, 0.06),
+        ];
+
+        let rated = glicko2.rate(&ratings, &[3, 4, 2, 1]);
+
+        assert_close(rated[0].value, 1464.06, 0.01);
+        assert_close(rated[0].deviation.unwrap(), 151.52, 0.01);
+        assert_close(rated[0].volatility.unwrap(), 0.05999, 0.00001);
+    }
+
+    /// Two default players in openskill.py (mu 25, sigma 25/3, no tau) end up with
+    /// mu 27.6352 / 22.3648 and sigma 8.0655, scaled by 60 here
+    #[test]
+    fn openskill_duel() {
+        let openskill = OpenSkill {
+            tau: 0.0,
+            ..OpenSkill::default()
+        };
+        let rated = openskill.rate(&[openskill.initial(), openskill.initial()], &[1, 2]);
+
+        assert_close(rated[0].value, 27.635231 * 60.0, 0.01);
+        assert_close(rated[1].value, 22.364769 * 60.0, 0.01);
+        assert_close(rated[0].deviation.unwrap(), 8.065506 * 60.0, 0.01);
+        assert_close(rated[1].deviation.unwrap(), 8.065506 * 60.0, 0.01);
+    }
+
+    #[test]
+    fn openskill_orders_ratings_by_placement() {
+        let openskill = OpenSkill::default();
+        let rated = openskill.rate(&[openskill.initial(); 4], &[2, 1, 4, 3]);
+
+        assert!(rated[1].value > rated[0].value);
+        assert!(rated[0].value > rated[3].value);
+        assert!(rated[3].value > rated[2].value);
+        assert!(rated.iter().all(|rating| rating.deviation.unwrap() < openskill.sigma));
+    }
+
+    #[test]
+    fn weighted_scales_change() {
+        let before = rating(1500.0, 200.0, 0.06);
+        let after = rating(1540.0, 180.0, 0.05);
+
+        let weighted = before.weighted(&after, 0.5);
+
+        assert_close(weighted.value, 1520.0, 1e-9);
+        assert_close(weighted.deviation.unwrap(), 190.0, 1e-9);
+        assert_close(weighted.volatility.unwrap(), 0.055, 1e-9);
+    }
+
+    #[test]
+    fn finishing_orders_sum_to_one() {
+        let elo = Elo::default();
+        let ratings = [elo.initial(); 4];
+
+        let orders = finishing_orders(&elo, &ratings);
+
+        assert_eq!(orders.len(), 24);
+        assert_close(orders.iter().map(|(_, probability)| probability).sum(), 1.0, 1e-9);
+        assert!(orders.iter().all(|(_, probability)| (probability - 1.0 / 24.0).abs() < 1e-9));
+    }
+}
@@ -0,0 +1,538 @@
+use crate::game_events::{
+    GameEventsFinishRoundByChonbo, GameEventsFinishRoundByRon, GameEventsFinishRoundByRyuukyoku,
+    GameEventsFinishRoundByTsumo,
+};
+
+pub const STARTING_POINTS: i64 = 25000;
+const RIICHI_STICK: i64 = 1000;
+const NOTEN_PENALTY: i64 = 3000;
+
+#[derive(Debug, Clone)]
+pub struct GameEvent {
+    pub event_type: String,
+    pub event_data: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandKind {
+    Tsumo,
+    Ron,
+    Ryuukyoku,
+    Chonbo,
+}
+
+/// Winner of the hand, value is what winner got paid excluding riichi sticks,
+/// basic are basic points of the hand (2000 = mangan, 8000 = yakuman)
+#[derive(Debug, Clone)]
+pub struct HandWin {
+    pub seat: usize,
+    pub value: i64,
+    pub basic: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hand {
+    pub kind: HandKind,
+    pub wins: Vec<HandWin>,
+    /// seat which dealt in on ron or committed chonbo
+    pub loser: Option<usize>,
+    pub riichi: Vec<usize>,
+}
+
+/// Final state of the table after replaying all game session events,
+/// seats are in game session order (player1 = starting east)
+#[derive(Debug, Clone)]
+pub struct GameOutcome {
+    pub points: [i64; 4],
+    pub placements: [u8; 4],
+    pub hands: Vec<Hand>,
+    /// dealer of the last played hand, 1-based
+    pub round: i64,
+    /// wind enum int-indexed => east = 0, south = 1, west = 2, north = 3
+    pub wind: i64,
+    pub started_at: i64,
+    pub ended_at: i64,
+}
+
+impl GameOutcome {
+    pub fn duration(&self) -> i64 {
+        (self.ended_at - self.started_at).max(0)
+    }
+}
+
+/// Basic points of the hand before dealer / non-dealer multipliers
+pub fn basic_points(han: i64, fu: i64, yakuman: i64) -> i64 {
+    if yakuman > 0 {
+        return 8000 * yakuman;
+    }
+
+    match han {
+        13.. => 8000,
+        11..=12 => 6000,
+        8..=10 => 4000,
+        6..=7 => 3000,
+        5 => 2000,
+        _ => {
+            // chiitoitsu is the only hand with fu not rounded up to tens
+            let fu = if fu == 25 { fu } else { ((fu.max(20) + 9) / 10) * 10 };
+
+            (fu * 2i64.pow((han.max(1) + 2) as u32)).min(2000)
+        }
+    }
+}
+
+fn round_up(points: i64) -> i64 {
+    ((points + 99) / 100) * 100
+}
+
+struct Table {
+    points: [i64; 4],
+    dealer: usize,
+    wind: usize,
+    honba: i64,
+    riichi_sticks: i64,
+}
+
+impl Table {
+    fn new() -> Self {
+        Self {
+            points: [STARTING_POINTS; 4],
+            dealer: 0,
+            wind: 0,
+            honba: 0,
+            riichi_sticks: 0,
+        }
+    }
+
+    fn rotate_dealer(&mut self) {
+        self.dealer += 1;
+
+        if self.dealer == 4 {
+            self.dealer = 0;
+            self.wind += 1;
+        }
+    }
+
+    fn declare_riichi(&mut self, seats: &[usize]) {
+        for &seat in seats {
+            self.points[seat] -= RIICHI_STICK;
+            self.riichi_sticks += 1;
+        }
+    }
+
+    fn collect_riichi_sticks(&mut self, seat: usize) {
+        self.points[seat] += self.riichi_sticks * RIICHI_STICK;
+        self.riichi_sticks = 0;
+    }
+
+    fn tsumo(&mut self, winner: usize, basic: i64) -> i64 {
+        let points_before = self.points[winner];
+
+        for seat in (0..4).filter(|&seat| seat != winner) {
+            let payment = if winner == self.dealer || seat == self.dealer {
+                round_up(basic * 2)
+            } else {
+                round_up(basic)
+            } + self.honba * 100;
+
+            self.points[seat] -= payment;
+            self.points[winner] += payment;
+        }
+
+        let value = self.points[winner] - points_before;
+
+        self.collect_riichi_sticks(winner);
+        self.finish_hand(winner == self.dealer);
+
+        value
+    }
+
+    fn ron(&mut self, wins: &[(usize, i64)], loser: usize) -> Vec<i64> {
+        let mut dealer_won = false;
+        let mut values = Vec::with_capacity(wins.len());
+
+        for (i, &(winner, basic)) in wins.iter().enumerate() {
+            let multiplier = if winner == self.dealer { 6 } else { 4 };
+            // honba goes only to the first winner (atamahane order)
+            let honba = if i == 0 { self.honba * 300 } else { 0 };
+            let payment = round_up(basic * multiplier) + honba;
+
+            self.points[loser] -= payment;
+            self.points[winner] += payment;
+            dealer_won |= winner == self.dealer;
+            values.push(payment);
+        }
+
+        if let Some(&(winner, _)) = wins.first() {
+            self.collect_riichi_sticks(winner);
+        }
+
+        self.finish_hand(dealer_won);
+
+        values
+    }
+
+    fn ryuukyoku(&mut self, tenpai: &[usize]) {
+        if !tenpai.is_empty() && tenpai.len() < 4 {
+            let received = NOTEN_PENALTY / tenpai.len() as i64;
+            let paid = NOTEN_PENALTY / (4 - tenpai.len()) as i64;
+
+            for seat in 0..4 {
+                if tenpai.contains(&seat) {
+                    self.points[seat] += received;
+                } else {
+                    self.points[seat] -= paid;
+                }
+            }
+        }
+
+        let dealer_tenpai = tenpai.contains(&self.dealer);
+
+        self.honba += 1;
+        if !dealer_tenpai {
+            self.rotate_dealer();
+        }
+    }
+
+    /// Offender pays reverse mangan, hand is replayed without repeat counter change
+    fn chonbo(&mut self, offender: usize) {
+        for seat in (0..4).filter(|&seat| seat != offender) {
+            let payment = if offender == self.dealer || seat == self.dealer {
+                4000
+            } else {
+                2000
+            };
+
+            self.points[offender] -= payment;
+            self.points[seat] += payment;
+        }
+    }
+
+    fn finish_hand(&mut self, dealer_won: bool) {
+        if dealer_won {
+            self.honba += 1;
+        } else {
+            self.honba = 0;
+            self.rotate_dealer();
+        }
+    }
+}
+
+fn seat_of(players: &[String; 4], player_uuid: &str) -> Result<usize, anyhow::Error> {
+    players
+        .iter()
+        .position(|uuid| uuid == player_uuid)
+        .ok_or_else(|| anyhow::Error::msg(format!("player [{}] is not seated at the table", player_uuid)))
+}
+
+fn seats_of(players: &[String; 4], players_uuids: &[String]) -> Result<Vec<usize>, anyhow::Error> {
+    players_uuids
+        .iter()
+        .map(|uuid| seat_of(players, uuid))
+        .collect()
+}
+
+fn hand_basic_points(han: Option<i64>, fu: Option<i64>, yakuman: Option<i64>) -> i64 {
+    basic_points(han.unwrap_or(0), fu.unwrap_or(0), yakuman.unwrap_or(0))
+}
+
+/// Strips events cancelled by `undo_last`, every `undo_last` cancels
+/// the most recent event which has not been cancelled yet
+pub fn effective_events(events: &[GameEvent]) -> Vec<&GameEvent> {
+    let mut stack: Vec<&GameEvent> = Vec::with_capacity(events.len());
+
+    for event in events {
+        if event.event_type == "undo_last" {
+            stack.pop();
+        } else {
+            stack.push(event);
+        }
+    }
+
+    stack
+}
+
+/// Replays game session events in order of creation,
+/// returns `None` when game has not ended yet or was undone
+pub fn replay(players: &[String; 4], events: &[GameEvent]) -> Result<Option<GameOutcome>, anyhow::Error> {
+    let events = effective_events(events);
+    let mut table = Table::new();
+    let mut started_at = None;
+    let mut last_hand = (0, 0);
+    let mut hands = Vec::new();
+
+    for event in events {
+        let data = event.event_data.as_deref().unwrap_or("null");
+
+        match event.event_type.as_str() {
+            "start" => {
+                started_at.get_or_insert(event.created_at);
+            }
+            "undo_game" => return Ok(None),
+            "end" => {
+                let placements = placements(&table.points);
+
+                return Ok(Some(GameOutcome {
+                    points: table.points,
+                    placements,
+                    hands,
+                    round: last_hand.0 as i64 + 1,
+                    wind: last_hand.1 as i64,
+                    started_at: started_at.unwrap_or(event.created_at),
+                    ended_at: event.created_at,
+                }));
+            }
+            "finish_round_by_tsumo" => {
+                let input = serde_json::from_str::<GameEventsFinishRoundByTsumo>(data)?;
+                let delta = input
+                    .delta
+                    .first()
+                    .ok_or_else(|| anyhow::Error::msg("tsumo without scoring player"))?;
+
+                let winner = seat_of(players, &delta.scoring_player_uuid)?;
+                let riichi = seats_of(players, &input.declared_riichi_player_uuids)?;
+
+                last_hand = (table.dealer, table.wind);
+                table.declare_riichi(&riichi);
+                let basic = hand_basic_points(delta.han, delta.fu, delta.yakuman);
+                let value = table.tsumo(winner, basic);
+
+                hands.push(Hand {
+                    kind: HandKind::Tsumo,
+                    wins: vec![HandWin { seat: winner, value, basic }],
+                    loser: None,
+                    riichi,
+                });
+            }
+            "finish_round_by_ron" => {
+                let input = serde_json::from_str::<GameEventsFinishRoundByRon>(data)?;
+                let loser = input
+                    .delta
+                    .first()
+                    .map(|delta| seat_of(players, &delta.losing_player_uuid))
+                    .ok_or_else(|| anyhow::Error::msg("ron without scoring player"))??;
+                let wins = input
+                    .delta
+                    .iter()
+                    .map(|delta| {
+                        seat_of(players, &delta.scoring_player_uuid)
+                            .map(|seat| (seat, hand_basic_points(delta.han, delta.fu, delta.yakuman)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let riichi = seats_of(players, &input.declared_riichi_player_uuids)?;
+
+                last_hand = (table.dealer, table.wind);
+                table.declare_riichi(&riichi);
+                let values = table.ron(&wins, loser);
+
+                hands.push(Hand {
+                    kind: HandKind::Ron,
+                    wins: wins
+                        .iter()
+                        .zip(values)
+                        .map(|(&(seat, basic), value)| HandWin { seat, value, basic })
+                        .collect(),
+                    loser: Some(loser),
+                    riichi,
+                });
+            }
+            "finish_round_by_ryuukyoku" => {
+                let input = serde_json::from_str::<GameEventsFinishRoundByRyuukyoku>(data)?;
+
+                let riichi = seats_of(players, &input.declared_riichi_player_uuids)?;
+
+                last_hand = (table.dealer, table.wind);
+                table.declare_riichi(&riichi);
+                table.ryuukyoku(&seats_of(players, &input.tenpai_player_uuids)?);
+
+                hands.push(Hand {
+                    kind: HandKind::Ryuukyoku,
+                    wins: Vec::new(),
+                    loser: None,
+                    riichi,
+                });
+            }
+            "finish_round_by_chonbo" => {
+                let input = serde_json::from_str::<GameEventsFinishRoundByChonbo>(data)?;
+
+                let offender = seat_of(players, &input.player_uuid)?;
+
+                last_hand = (table.dealer, table.wind);
+                table.chonbo(offender);
+
+                hands.push(Hand {
+                    kind: HandKind::Chonbo,
+                    wins: Vec::new(),
+                    loser: Some(offender),
+                    riichi: Vec::new(),
+                });
+            }
+            other => tracing::warn!("skipping unknown game event type [{}]", other),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Placements by points, ties are broken by seat order (starting east first)
+pub fn placements(points: &[i64; 4]) -> [u8; 4] {
+    let mut order = [0usize, 1, 2, 3];
+    order.sort_by(|&a, &b| points[b].cmp(&points[a]).then(a.cmp(&b)));
+
+    let mut placements = [0u8; 4];
+    for (place, seat) in order.into_iter().enumerate() {
+        placements[seat] = place as u8 + 1;
+    }
+
+    placements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn players() -> [String; 4] {
+        ["p0", "p1", "p2", "p3"].map(String::from)
+    }
+
+    fn event(event_type: &str, event_data: serde_json::Value) -> GameEvent {
+        GameEvent {
+            event_type: event_type.to_string(),
+            event_data: Some(event_data.to_string()),
+            created_at: 0,
+        }
+    }
+
+    fn tsumo(winner: &str, han: i64, fu: i64, riichi: &[&str]) -> GameEvent {
+        event(
+            "finish_round_by_tsumo",
+            json!({
+                "delta": [{ "scoring_player_uuid": winner, "han": han, "fu": fu }],
+                "declared_riichi_player_uuids": riichi,
+            }),
+        )
+    }
+
+    fn ron(wins: &[(&str, i64, i64)], loser: &str, riichi: &[&str]) -> GameEvent {
+        let delta = wins
+            .iter()
+            .map(|(winner, han, fu)| {
+                json!({ "scoring_player_uuid": winner, "losing_player_uuid": loser, "han": han, "fu": fu })
+            })
+            .collect::<Vec<_>>();
+
+        event(
+            "finish_round_by_ron",
+            json!({ "delta": delta, "declared_riichi_player_uuids": riichi }),
+        )
+    }
+
+    fn ryuukyoku(tenpai: &[&str], riichi: &[&str]) -> GameEvent {
+        event(
+            "finish_round_by_ryuukyoku",
+            json!({ "tenpai_player_uuids": tenpai, "declared_riichi_player_uuids": riichi }),
+        )
+    }
+
+    fn replay_game(events: Vec<GameEvent>) -> GameOutcome {
+        let mut events = events;
+        events.insert(0, event("start", json!(null)));
+        events.push(event("end", json!(null)));
+
+        replay(&players(), &events).unwrap().unwrap()
+    }
+
+    #[test]
+    fn basic_points_limits() {
+        assert_eq!(basic_points(3, 30, 0), 960);
+        assert_eq!(basic_points(4, 40, 0), 2000);
+        assert_eq!(basic_points(2, 25, 0), 400);
+        assert_eq!(basic_points(1, 22, 0), 240);
+        assert_eq!(basic_points(6, 30, 0), 3000);
+        assert_eq!(basic_points(13, 30, 0), 8000);
+        assert_eq!(basic_points(0, 0, 2), 16000);
+    }
+
+    #[test]
+    fn replay_non_dealer_tsumo() {
+        let outcome = replay_game(vec![tsumo("p1", 3, 30, &[])]);
+
+        // dealer pays 1920 rounded up, non-dealers 960 rounded up
+        assert_eq!(outcome.points, [23000, 29000, 24000, 24000]);
+        assert_eq!(outcome.hands[0].wins[0].value, 4000);
+        // tie between p2 and p3 goes to the earlier seat
+        assert_eq!(outcome.placements, [4, 1, 2, 3]);
+    }
+
+    #[test]
+    fn replay_dealer_tsumo_with_honba() {
+        let outcome = replay_game(vec![ryuukyoku(&["p0"], &[]), tsumo("p0", 4, 30, &[])]);
+
+        // 3000 for tenpai, then 3900 + 100 honba from everyone
+        assert_eq!(outcome.points, [40000, 20000, 20000, 20000]);
+        assert_eq!(outcome.hands[1].wins[0].value, 12000);
+        // dealer kept the seat for both hands
+        assert_eq!((outcome.round, outcome.wind), (1, 0));
+    }
+
+    #[test]
+    fn replay_double_ron_pays_sticks_to_first_winner() {
+        let outcome = replay_game(vec![
+            ryuukyoku(&["p2", "p3"], &["p2", "p3"]),
+            ron(&[("p2", 2, 30), ("p1", 5, 30)], "p3", &[]),
+        ]);
+
+        // p2: -1000 riichi +1500 tenpai +2000 ron +300 honba +2000 sticks
+        // p1 (dealer after p0 was noten): -1500 noten +12000 mangan
+        assert_eq!(outcome.points, [23500, 35500, 29800, 11200]);
+        assert_eq!(outcome.hands[1].wins[0].value, 2300);
+        assert_eq!(outcome.hands[1].wins[1].value, 12000);
+        assert_eq!(outcome.hands[1].loser, Some(3));
+        assert_eq!((outcome.round, outcome.wind), (2, 0));
+    }
+
+    #[test]
+    fn replay_noten_payments() {
+        let outcome = replay_game(vec![ryuukyoku(&["p1"], &[]), ryuukyoku(&[], &[])]);
+
+        assert_eq!(outcome.points, [24000, 28000, 24000, 24000]);
+        assert_eq!((outcome.round, outcome.wind), (2, 0));
+    }
+
+    #[test]
+    fn replay_chonbo_keeps_dealer() {
+        let outcome = replay_game(vec![
+            event("finish_round_by_chonbo", json!({ "player_uuid": "p2" })),
+            tsumo("p0", 5, 30, &[]),
+        ]);
+
+        // reverse mangan: 4000 to dealer, 2000 to others; then dealer mangan tsumo
+        assert_eq!(outcome.points, [41000, 23000, 13000, 23000]);
+        assert_eq!(outcome.hands[0].kind, HandKind::Chonbo);
+        assert_eq!(outcome.hands[0].loser, Some(2));
+        assert_eq!((outcome.round, outcome.wind), (1, 0));
+    }
+
+    #[test]
+    fn replay_skips_undone_events() {
+        let outcome = replay_game(vec![
+            tsumo("p1", 3, 30, &[]),
+            event("undo_last", json!(null)),
+            tsumo("p2", 3, 30, &[]),
+        ]);
+
+        assert_eq!(outcome.points, [23000, 24000, 29000, 24000]);
+
+        let undone = [tsumo("p1", 3, 30, &[]), event("undo_game", json!(null))];
+        assert!(replay(&players(), &undone).unwrap().is_none());
+    }
+
+    #[test]
+    fn placements_break_ties_by_seat() {
+        assert_eq!(placements(&[25000; 4]), [1, 2, 3, 4]);
+        assert_eq!(placements(&[20000, 30000, 30000, 20000]), [3, 1, 2, 4]);
+    }
+}
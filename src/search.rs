@@ -0,0 +1,151 @@
+use sqlx::{SqliteConnection, SqlitePool};
+use tracing::info;
+use unicode_normalization::UnicodeNormalization;
+
+/// Folds text for accent and case insensitive comparison,
+/// i.e. `Poznań` => `poznan`, `Łódź` => `lodz`
+pub fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !unicode_normalization::char::is_combining_mark(*c))
+        .flat_map(|c| c.to_lowercase())
+        // letters with stroke are not decomposed by nfd
+        .map(|c| match c {
+            'ł' => 'l',
+            'đ' => 'd',
+            'ø' => 'o',
+            'ħ' => 'h',
+            c => c,
+        })
+        .collect()
+}
+
+/// Space separated list of folded words, prefixed with space,
+/// so word prefix can be matched with `LIKE '% prefix%'`
+pub fn search_terms(fields: &[Option<&str>]) -> String {
+    fields
+        .iter()
+        .flatten()
+        .flat_map(|field| field.split_whitespace())
+        .map(|word| format!(" {}", fold(word)))
+        .collect()
+}
+
+/// Search terms of the player, names are searchable only with gdpr consent
+pub fn player_search_terms(
+    usma_id: &str,
+    nickname: Option<&str>,
+    first_name: Option<&str>,
+    last_name: Option<&str>,
+    is_gdpr_agreed: bool,
+) -> String {
+    if is_gdpr_agreed {
+        search_terms(&[nickname, first_name, last_name, Some(usma_id)])
+    } else {
+        search_terms(&[nickname, Some(usma_id)])
+    }
+}
+
+/// Pattern matching any word starting with folded `prefix`
+pub fn word_prefix_pattern(prefix: &str) -> String {
+    let escaped = fold(prefix.trim())
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("% {}%", escaped)
+}
+
+/// FTS5 query matching every word of `text` as a prefix of any word of the indexed fields,
+/// punctuation is dropped so user input can't use the query syntax
+pub fn match_query(text: &str) -> Option<String> {
+    let words = fold(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect::<Vec<_>>();
+
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// Replaces indexed name, street and city of the place with the current ones
+pub async fn index_place(conn: &mut SqliteConnection, place_uuid: &str) -> Result<(), sqlx::Error> {
+    let place = sqlx::query!("SELECT name, street, city FROM places WHERE uuid = ?", place_uuid)
+        .fetch_one(&mut *conn)
+        .await?;
+    let name = fold(&place.name);
+    let street = place.street.as_deref().map(fold);
+    let city = fold(&place.city);
+
+    sqlx::query!("DELETE FROM places_search WHERE place_uuid = ?", place_uuid)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO places_search (place_uuid, name, street, city) VALUES (?, ?, ?, ?)",
+        place_uuid,
+        name,
+        street,
+        city,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Indexes places inserted without the application, i.e. by data seed
+pub async fn backfill_places_search(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+
+    let places_uuids = sqlx::query_scalar!(
+        r#"SELECT uuid as "uuid!" FROM places
+        WHERE uuid NOT IN (SELECT place_uuid FROM places_search)"#
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    for place_uuid in &places_uuids {
+        index_place(&mut conn, place_uuid).await?;
+    }
+
+    info!("indexed {} places", places_uuids.len());
+
+    Ok(())
+}
+
+/// Fills search terms of players inserted without them, i.e. by data seed
+pub async fn backfill_players_search_terms(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+
+    let players = sqlx::query!(
+        r#"SELECT uuid, usma_id, first_name, last_name, nickname, is_gdpr_agreed as "is_gdpr_agreed: bool"
+        FROM players_cache WHERE search_terms = ''"#
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    for player in &players {
+        let terms = player_search_terms(
+            &player.usma_id,
+            player.nickname.as_deref(),
+            player.first_name.as_deref(),
+            player.last_name.as_deref(),
+            player.is_gdpr_agreed,
+        );
+
+        sqlx::query!(
+            "UPDATE players_cache SET search_terms = ? WHERE uuid = ?",
+            terms,
+            player.uuid
+        )
+        .execute(&mut conn)
+        .await?;
+    }
+
+    info!("filled search terms of {} players", players.len());
+
+    Ok(())
+}
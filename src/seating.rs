@@ -0,0 +1,355 @@
+//! Seating of tournament rounds. Players are split into tables of four, then players are
+//! swapped between tables while it lowers the number of repeated opponents, and finally
+//! seats at every table are picked so each player sits on the winds they had the least.
+//! Teammates count as opponents met many times, so they end up at different tables whenever possible.
+//!
+//! League fixtures use an exact round-robin instead, when the number of entrants allows one
+//! (see `round_robin`).
+
+use hashbrown::{HashMap, HashSet};
+
+// passes over all table pairs, swapping stops earlier when nothing improves
+const MAX_PASSES: usize = 20;
+// repeats counted for teammates at the same table, outweighs any number of rounds
+const TEAMMATES_REPEATS: i64 = 1000;
+
+/// Previous rounds of the tournament
+#[derive(Debug, Default)]
+pub struct SeatingHistory {
+    // games played against each other, keyed by ordered pair of players
+    opponents: HashMap<(String, String), i64>,
+    // games played on each seat, east first
+    seats: HashMap<String, [i64; 4]>,
+    byes: HashMap<String, i64>,
+    // ordered pairs of players of the same team
+    teammates: HashSet<(String, String)>,
+}
+
+impl SeatingHistory {
+    pub fn add_table(&mut self, players: &[String; 4]) {
+        for (seat, player) in players.iter().enumerate() {
+            self.seats.entry(player.clone()).or_default()[seat] += 1;
+
+            for opponent in &players[seat + 1..] {
+                *self.opponents.entry(pair(player, opponent)).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn add_bye(&mut self, player: &str) {
+        *self.byes.entry(player.to_string()).or_default() += 1;
+    }
+
+    /// Players of one team, kept at different tables
+    pub fn add_team(&mut self, players: &[String]) {
+        for (index, player) in players.iter().enumerate() {
+            for teammate in &players[index + 1..] {
+                self.teammates.insert(pair(player, teammate));
+            }
+        }
+    }
+
+    pub fn byes(&self, player: &str) -> i64 {
+        self.byes.get(player).copied().unwrap_or(0)
+    }
+
+    fn met(&self, a: &str, b: &str) -> i64 {
+        let pair = pair(a, b);
+        let teammates = if self.teammates.contains(&pair) { TEAMMATES_REPEATS } else { 0 };
+
+        self.opponents.get(&pair).copied().unwrap_or(0) + teammates
+    }
+
+    fn seats(&self, player: &str) -> [i64; 4] {
+        self.seats.get(player).copied().unwrap_or_default()
+    }
+
+    /// Repeated opponents of `player` if seated with `others`, `player` itself is skipped
+    fn repeats(&self, player: &str, others: &[String]) -> i64 {
+        others
+            .iter()
+            .filter(|other| *other != player)
+            .map(|other| self.met(player, other))
+            .sum()
+    }
+}
+
+fn pair(a: &str, b: &str) -> (String, String) {
+    if a < b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+/// Players sitting the round out, the ones with fewest byes so far and,
+/// as `players` are in order of preference, the last ones among them
+pub fn pick_byes(players: &[String], count: usize, history: &SeatingHistory) -> Vec<String> {
+    let mut candidates = players.iter().enumerate().collect::<Vec<_>>();
+    candidates.sort_by_key(|(index, player)| (history.byes(player), std::cmp::Reverse(*index)));
+
+    candidates
+        .into_iter()
+        .take(count)
+        .map(|(_, player)| player.clone())
+        .collect()
+}
+
+/// Splits players (count must be a multiple of four) into tables in their order.
+/// with `is_swiss` only neighbouring tables swap players, so tables keep players of similar
+/// standing, unless the swap separates teammates, otherwise players move between any tables
+pub fn seat(players: &[String], history: &SeatingHistory, is_swiss: bool) -> Vec<[String; 4]> {
+    let mut tables = players
+        .chunks(4)
+        .map(|chunk| chunk.to_vec())
+        .collect::<Vec<_>>();
+
+    for _ in 0..MAX_PASSES {
+        let mut is_improved = false;
+
+        for i in 0..tables.len() {
+            for j in (i + 1)..tables.len() {
+                let min_gain = if is_swiss && j > i + 1 { TEAMMATES_REPEATS } else { 1 };
+
+                for a in 0..4 {
+                    for b in 0..4 {
+                        if swap_gain(&tables[i], &tables[j], a, b, history) >= min_gain {
+                            let player = std::mem::take(&mut tables[i][a]);
+                            tables[i][a] = std::mem::replace(&mut tables[j][b], player);
+                            is_improved = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        if !is_improved {
+            break;
+        }
+    }
+
+    tables
+        .into_iter()
+        .map(|table| assign_seats(table, history))
+        .collect()
+}
+
+// GF(4) multiplication, elements are polynomials over GF(2) modulo x^2 + x + 1
+// encoded as bits (2 = x, 3 = x + 1), addition is xor
+const GF4_MUL: [[usize; 4]; 4] = [[0, 0, 0, 0], [0, 1, 2, 3], [0, 2, 3, 1], [0, 3, 1, 2]];
+
+/// Rounds of tables of four in which every pair of players meets exactly once,
+/// `None` unless the number of players is a power of four.
+/// Players are points of the affine space over GF(4) (their index in base 4), tables are its
+/// lines and every round is a class of parallel lines, so it has (players - 1) / 3 rounds.
+/// Seats are not assigned, see `assign_seats`
+pub fn round_robin(players: &[String]) -> Option<Vec<Vec<Vec<String>>>> {
+    let mut dimensions = 0;
+    while 4usize.pow(dimensions) < players.len() {
+        dimensions += 1;
+    }
+
+    if dimensions == 0 || 4usize.pow(dimensions) != players.len() {
+        return None;
+    }
+
+    let digits = |point: usize| (0..dimensions).map(move |i| (point >> (2 * i)) & 3);
+    // directions with the last nonzero digit equal to one, one per class of parallel lines
+    let directions = (1..players.len())
+        .filter(|&direction| digits(direction).rev().find(|&digit| digit != 0) == Some(1))
+        .collect::<Vec<_>>();
+
+    let rounds = directions
+        .into_iter()
+        .map(|direction| {
+            let mut is_seated = vec![false; players.len()];
+            let mut tables = Vec::with_capacity(players.len() / 4);
+
+            for start in 0..players.len() {
+                if is_seated[start] {
+                    continue;
+                }
+
+                let table = (0..4)
+                    .map(|t| {
+                        digits(direction)
+                            .enumerate()
+                            .fold(start, |point, (i, digit)| point ^ (GF4_MUL[t][digit] << (2 * i)))
+                    })
+                    .collect::<Vec<_>>();
+
+                for &point in &table {
+                    is_seated[point] = true;
+                }
+                tables.push(table.into_iter().map(|point| players[point].clone()).collect());
+            }
+
+            tables
+        })
+        .collect();
+
+    Some(rounds)
+}
+
+/// Fewer repeated opponents after swapping player `a` of the first table with `b` of the second
+fn swap_gain(first: &[String], second: &[String], a: usize, b: usize, history: &SeatingHistory) -> i64 {
+    let (player_a, player_b) = (&first[a], &second[b]);
+    let before = history.repeats(player_a, first) + history.repeats(player_b, second);
+
+    let first_after = first
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != a)
+        .map(|(_, player)| player.clone())
+        .collect::<Vec<_>>();
+    let second_after = second
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != b)
+        .map(|(_, player)| player.clone())
+        .collect::<Vec<_>>();
+    let after = history.repeats(player_b, &first_after) + history.repeats(player_a, &second_after);
+
+    before - after
+}
+
+/// Order of players at the table, east first, which puts players on the seats they sat the least
+pub fn assign_seats(table: Vec<String>, history: &SeatingHistory) -> [String; 4] {
+    let seats = table.iter().map(|player| history.seats(player)).collect::<Vec<_>>();
+    let mut best = ([0, 1, 2, 3], i64::MAX);
+
+    for permutation in permutations() {
+        // squares favour spreading seats over fixing the worst one
+        let cost = permutation
+            .iter()
+            .enumerate()
+            .map(|(seat, &player)| (seats[player][seat] + 1).pow(2))
+            .sum::<i64>();
+
+        if cost < best.1 {
+            best = (permutation, cost);
+        }
+    }
+
+    best.0.map(|player| table[player].clone())
+}
+
+/// Every order of four players, player at index is seated at the seat of the index
+fn permutations() -> Vec<[usize; 4]> {
+    let mut permutations = Vec::with_capacity(24);
+
+    for a in 0..4 {
+        for b in (0..4).filter(|&b| b != a) {
+            for c in (0..4).filter(|&c| c != a && c != b) {
+                let d = 6 - a - b - c;
+
+                permutations.push([a, b, c, d]);
+            }
+        }
+    }
+
+    permutations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players(count: usize) -> Vec<String> {
+        (0..count).map(|index| format!("player-{:02}", index)).collect()
+    }
+
+    #[test]
+    fn seat_avoids_repeated_opponents() {
+        let players = players(16);
+        let mut history = SeatingHistory::default();
+
+        for _ in 0..3 {
+            for table in seat(&players, &history, false) {
+                history.add_table(&table);
+            }
+        }
+
+        assert!(history.opponents.values().all(|&games| games == 1));
+        assert_eq!(history.opponents.len(), 16 * 9 / 2);
+    }
+
+    #[test]
+    fn seat_rotates_winds() {
+        let players = players(4);
+        let mut history = SeatingHistory::default();
+
+        for _ in 0..4 {
+            for table in seat(&players, &history, false) {
+                history.add_table(&table);
+            }
+        }
+
+        for player in &players {
+            assert_eq!(history.seats(player), [1, 1, 1, 1], "{}", player);
+        }
+    }
+
+    #[test]
+    fn seat_separates_teammates() {
+        let players = players(8);
+        let mut history = SeatingHistory::default();
+        history.add_team(&players[..4]);
+
+        for table in seat(&players, &history, true) {
+            assert_eq!(table.iter().filter(|player| players[..4].contains(player)).count(), 2);
+        }
+    }
+
+    #[test]
+    fn round_robin_meets_everyone_once() {
+        for count in [4, 16, 64] {
+            let players = players(count);
+            let mut history = SeatingHistory::default();
+
+            let rounds = round_robin(&players).unwrap();
+
+            assert_eq!(rounds.len(), (count - 1) / 3);
+            for round in rounds {
+                assert_eq!(round.len(), count / 4);
+                assert_eq!(round.iter().flatten().collect::<HashSet<_>>().len(), count);
+
+                for table in round {
+                    history.add_table(&assign_seats(table, &history));
+                }
+            }
+
+            assert!(history.opponents.values().all(|&games| games == 1));
+            assert_eq!(history.opponents.len(), count * (count - 1) / 2);
+        }
+    }
+
+    #[test]
+    fn round_robin_needs_power_of_four() {
+        assert!(round_robin(&players(0)).is_none());
+        assert!(round_robin(&players(8)).is_none());
+        assert!(round_robin(&players(28)).is_none());
+    }
+
+    #[test]
+    fn pick_byes_rotates() {
+        let players = players(5);
+        let mut history = SeatingHistory::default();
+
+        for _ in 0..5 {
+            for player in pick_byes(&players, 1, &history) {
+                history.add_bye(&player);
+            }
+        }
+
+        assert!(players.iter().all(|player| history.byes(player) == 1));
+    }
+
+    #[test]
+    fn pick_byes_prefers_last_players() {
+        let players = players(6);
+        let history = SeatingHistory::default();
+
+        assert_eq!(pick_byes(&players, 2, &history), vec!["player-05", "player-04"]);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::scoring::{GameOutcome, HandKind};
+
+/// Statistics of a player accumulated over computed games
+#[derive(Debug, Default)]
+pub struct PlayerStats {
+    pub games: i64,
+    pub placements: [i64; 4],
+    pub hands: i64,
+    pub wins: i64,
+    pub tsumo: i64,
+    pub ron: i64,
+    pub deal_ins: i64,
+    pub riichi: i64,
+    pub chonbo: i64,
+    pub hand_value_sum: i64,
+    pub best_game: Option<(String, i64)>,
+    pub worst_game: Option<(String, i64)>,
+}
+
+impl PlayerStats {
+    pub fn add_game(&mut self, game_session_uuid: &str, seat: usize, outcome: &GameOutcome) {
+        let points = outcome.points[seat];
+
+        self.games += 1;
+        self.placements[outcome.placements[seat] as usize - 1] += 1;
+
+        if self.best_game.as_ref().is_none_or(|(_, best)| points > *best) {
+            self.best_game = Some((game_session_uuid.to_string(), points));
+        }
+        if self.worst_game.as_ref().is_none_or(|(_, worst)| points < *worst) {
+            self.worst_game = Some((game_session_uuid.to_string(), points));
+        }
+
+        for hand in &outcome.hands {
+            if hand.kind == HandKind::Chonbo {
+                if hand.loser == Some(seat) {
+                    self.chonbo += 1;
+                }
+
+                continue;
+            }
+
+            self.hands += 1;
+
+            if hand.riichi.contains(&seat) {
+                self.riichi += 1;
+            }
+            if hand.kind == HandKind::Ron && hand.loser == Some(seat) {
+                self.deal_ins += 1;
+            }
+
+            for win in hand.wins.iter().filter(|win| win.seat == seat) {
+                self.wins += 1;
+                self.hand_value_sum += win.value;
+
+                match hand.kind {
+                    HandKind::Tsumo => self.tsumo += 1,
+                    _ => self.ron += 1,
+                }
+            }
+        }
+    }
+
+    pub fn average_hand_value(&self) -> Option<f64> {
+        if self.wins > 0 {
+            Some(self.hand_value_sum as f64 / self.wins as f64)
+        } else {
+            None
+        }
+    }
+}
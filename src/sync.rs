@@ -0,0 +1,564 @@
+//! Synchronization of `rankings_cache`, `ranks_cache` and `players_cache` with federation export.
+//!
+//! Export is either a single json file:
+//!
+//! ```json
+//! {
+//!     "rankings": [{"uuid", "name", "created_at", "archived_at"}],
+//!     "ranks": [{"uuid", "ranking_uuid", "name", "required_points", "required_exam", "color", "created_at"}],
+//!     "players": [{
+//!         "uuid", "ranking_uuid", "usma_id", "ema_id", "first_name", "last_name", "city", "region",
+//!         "country_code", "nickname", "is_exam_done", "is_gdpr_agreed", "is_static", "created_at"
+//!     }]
+//! }
+//! ```
+//!
+//! or a directory with `rankings.csv`, `ranks.csv` and `players.csv` with the same fields as
+//! header row. Booleans are `true` / `false`, timestamps are unix seconds, empty field is NULL.
+//!
+//! Export is the source of truth - records missing locally are inserted, changed ones updated
+//! and those missing in the export are soft-deleted. Guests are local only and never touched,
+//! erased players are never restored. `ranking_snapshot_cache` is not imported,
+//! it's computed locally from game sessions (see compute.rs).
+//!
+//! `country_code` is either ISO 3166-1 code or english country name, players with unknown country
+//! are rejected and their local record is left as is, their uuids are listed in the report.
+//! `region_code` is derived from free-text `region` (see geo.rs). `ema_id` is optional,
+//! players without it can't be exported in EMA results.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use hashbrown::{HashMap, HashSet};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::{compute::SharedComputeService, geo, search};
+
+pub type SharedSyncService = Arc<SyncService>;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SyncRanking {
+    pub uuid: String,
+    pub name: String,
+    pub created_at: i64,
+    pub archived_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SyncRank {
+    pub uuid: String,
+    pub ranking_uuid: String,
+    pub name: String,
+    pub required_points: i64,
+    pub required_exam: bool,
+    pub color: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SyncPlayer {
+    pub uuid: String,
+    pub ranking_uuid: String,
+    pub usma_id: String,
+    #[serde(default)]
+    pub ema_id: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub city: Option<String>,
+    pub region: Option<String>,
+    pub country_code: String,
+    pub nickname: Option<String>,
+    pub is_exam_done: bool,
+    pub is_gdpr_agreed: bool,
+    pub is_static: bool,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SyncExport {
+    pub rankings: Vec<SyncRanking>,
+    pub ranks: Vec<SyncRank>,
+    pub players: Vec<SyncPlayer>,
+}
+
+impl SyncExport {
+    /// Reads json file or directory with csv files, every section must be present
+    /// as a missing one would soft-delete all of its records
+    pub fn read(source: &Path) -> Result<Self, anyhow::Error> {
+        if source.is_dir() {
+            Ok(Self {
+                rankings: read_csv(&source.join("rankings.csv"))?,
+                ranks: read_csv(&source.join("ranks.csv"))?,
+                players: read_csv(&source.join("players.csv"))?,
+            })
+        } else {
+            let file = std::fs::File::open(source)?;
+
+            Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+        }
+    }
+}
+
+fn read_csv<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, anyhow::Error> {
+    if !path.exists() {
+        return Err(anyhow::Error::msg(format!("{}: file not found", path.display())));
+    }
+
+    csv::Reader::from_path(path)?
+        .deserialize()
+        .collect::<Result<Vec<T>, _>>()
+        .map_err(|err| anyhow::Error::msg(format!("{}: {}", path.display(), err)))
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct SyncCounts {
+    pub inserted: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+    // records left untouched because of invalid values
+    pub rejected: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SyncReport {
+    pub uuid: String,
+    pub source: String,
+    pub is_dry_run: bool,
+    pub rankings: SyncCounts,
+    pub ranks: SyncCounts,
+    pub players: SyncCounts,
+    pub rejected_players_uuids: Vec<String>,
+    pub error: Option<String>,
+    pub started_at: i64,
+    pub finished_at: i64,
+}
+
+/// Records to be written, deleted are uuids of local records missing in the export
+struct Diff<'a, T> {
+    inserted: Vec<&'a T>,
+    updated: Vec<&'a T>,
+    deleted: Vec<String>,
+    unchanged: usize,
+}
+
+impl<'a, T> Diff<'a, T> {
+    fn counts(&self) -> SyncCounts {
+        SyncCounts {
+            inserted: self.inserted.len(),
+            updated: self.updated.len(),
+            deleted: self.deleted.len(),
+            unchanged: self.unchanged,
+            rejected: 0,
+        }
+    }
+}
+
+/// Local record with its soft-delete state, deleted record present in the export is restored
+struct Local<T> {
+    record: T,
+    is_deleted: bool,
+}
+
+fn diff<'a, T: PartialEq>(
+    local: &HashMap<String, Local<T>>,
+    remote: &'a [T],
+    uuid: impl Fn(&T) -> &str,
+) -> Diff<'a, T> {
+    let mut diff = Diff {
+        inserted: Vec::new(),
+        updated: Vec::new(),
+        deleted: Vec::new(),
+        unchanged: 0,
+    };
+    let remote_uuids = remote.iter().map(&uuid).collect::<HashSet<_>>();
+
+    for record in remote {
+        match local.get(uuid(record)) {
+            None => diff.inserted.push(record),
+            Some(local) if local.is_deleted || local.record != *record => diff.updated.push(record),
+            Some(_) => diff.unchanged += 1,
+        }
+    }
+
+    diff.deleted = local
+        .iter()
+        .filter(|(uuid, local)| !local.is_deleted && !remote_uuids.contains(uuid.as_str()))
+        .map(|(uuid, _)| uuid.clone())
+        .collect();
+    diff.deleted.sort();
+
+    diff
+}
+
+pub struct SyncService {
+    pool: SqlitePool,
+    compute: SharedComputeService,
+    source: Option<PathBuf>,
+    // periodic and on demand syncs must not overlap
+    lock: Mutex<()>,
+}
+
+impl SyncService {
+    pub fn new(pool: SqlitePool, compute: SharedComputeService, source: Option<PathBuf>) -> Self {
+        Self {
+            pool,
+            compute,
+            source,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Imports the export and stores report of the run, failed run is reported as well
+    pub async fn sync(&self, is_dry_run: bool) -> Result<SyncReport, anyhow::Error> {
+        let source = self
+            .source
+            .as_ref()
+            .ok_or_else(|| anyhow::Error::msg("sync source is not configured"))?;
+        let _guard = self.lock.lock().await;
+
+        let mut report = SyncReport {
+            uuid: uuid::Uuid::new_v4().as_hyphenated().to_string(),
+            source: source.display().to_string(),
+            is_dry_run,
+            started_at: chrono::Utc::now().timestamp(),
+            ..Default::default()
+        };
+
+        let result = match SyncExport::read(source) {
+            Ok(export) => self.apply(&export, &mut report).await,
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = &result {
+            report.error = Some(err.to_string());
+        }
+        report.finished_at = chrono::Utc::now().timestamp();
+
+        let counts = serde_json::to_string(&serde_json::json!({
+            "rankings": report.rankings,
+            "ranks": report.ranks,
+            "players": report.players,
+        }))?;
+        let rejected_players_uuids = serde_json::to_string(&report.rejected_players_uuids)?;
+
+        sqlx::query!(
+            "INSERT INTO sync_reports (
+                uuid, source, is_dry_run, counts, rejected_players_uuids, error, started_at, finished_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            report.uuid,
+            report.source,
+            report.is_dry_run,
+            counts,
+            rejected_players_uuids,
+            report.error,
+            report.started_at,
+            report.finished_at,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        info!(
+            "synced [{}] rankings {:?} ranks {:?} players {:?} error {:?}",
+            report.source, report.rankings, report.ranks, report.players, report.error
+        );
+
+        Ok(report)
+    }
+
+    async fn apply(&self, export: &SyncExport, report: &mut SyncReport) -> Result<(), anyhow::Error> {
+        let mut tx = self.pool.begin().await?;
+        let now = report.started_at;
+
+        // write first so the transaction holds the lock and local state
+        // can't change between the diff and applying it
+        sqlx::query!("UPDATE rankings_cache SET deleted_at = deleted_at WHERE 0")
+            .execute(&mut tx)
+            .await?;
+
+        let local_rankings = sqlx::query!(
+            r#"SELECT uuid, name, created_at, archived_at, deleted_at FROM rankings_cache"#
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let local = Local {
+                record: SyncRanking {
+                    uuid: row.uuid.clone(),
+                    name: row.name,
+                    created_at: row.created_at,
+                    archived_at: row.archived_at,
+                },
+                is_deleted: row.deleted_at.is_some(),
+            };
+
+            (row.uuid, local)
+        })
+        .collect::<HashMap<_, _>>();
+
+        let local_ranks = sqlx::query!(
+            r#"SELECT
+                uuid, ranking_uuid, name, required_points, required_exam as "required_exam: bool",
+                color, created_at, deleted_at
+            FROM ranks_cache"#
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let local = Local {
+                record: SyncRank {
+                    uuid: row.uuid.clone(),
+                    ranking_uuid: row.ranking_uuid,
+                    name: row.name,
+                    required_points: row.required_points,
+                    required_exam: row.required_exam,
+                    color: row.color,
+                    created_at: row.created_at,
+                },
+                is_deleted: row.deleted_at.is_some(),
+            };
+
+            (row.uuid, local)
+        })
+        .collect::<HashMap<_, _>>();
+
+        // guests are created locally and erased players must stay pseudonymized
+        let local_players = sqlx::query!(
+            r#"SELECT
+                uuid, ranking_uuid, usma_id, ema_id, first_name, last_name, city, region, country_code,
+                nickname, is_exam_done as "is_exam_done: bool", is_gdpr_agreed as "is_gdpr_agreed: bool",
+                is_static as "is_static: bool", created_at, deleted_at
+            FROM players_cache WHERE is_guest = 0 AND erased_at IS NULL"#
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let local = Local {
+                record: SyncPlayer {
+                    uuid: row.uuid.clone(),
+                    ranking_uuid: row.ranking_uuid,
+                    usma_id: row.usma_id,
+                    ema_id: row.ema_id,
+                    first_name: row.first_name,
+                    last_name: row.last_name,
+                    city: row.city,
+                    region: row.region,
+                    country_code: row.country_code,
+                    nickname: row.nickname,
+                    is_exam_done: row.is_exam_done,
+                    is_gdpr_agreed: row.is_gdpr_agreed,
+                    is_static: row.is_static,
+                    created_at: row.created_at,
+                },
+                is_deleted: row.deleted_at.is_some(),
+            };
+
+            (row.uuid, local)
+        })
+        .collect::<HashMap<_, _>>();
+
+        let erased_players = sqlx::query_scalar!(
+            "SELECT uuid FROM players_cache WHERE is_guest = 1 OR erased_at IS NOT NULL"
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+        // players with unknown country are neither written nor deleted,
+        // country codes of the others are stored normalized
+        let mut rejected_players = HashSet::new();
+        let players = export
+            .players
+            .iter()
+            .filter(|player| !erased_players.contains(&player.uuid))
+            .filter_map(|player| match geo::normalize_country(&player.country_code) {
+                Some(country_code) => Some(SyncPlayer {
+                    country_code: country_code.to_string(),
+                    ..player.clone()
+                }),
+                None => {
+                    warn!("rejected player [{}] with unknown country [{}]", player.uuid, player.country_code);
+                    rejected_players.insert(player.uuid.clone());
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let local_players = local_players
+            .into_iter()
+            .filter(|(uuid, _)| !rejected_players.contains(uuid))
+            .collect::<HashMap<_, _>>();
+
+        let rankings = diff(&local_rankings, &export.rankings, |ranking| &ranking.uuid);
+        let ranks = diff(&local_ranks, &export.ranks, |rank| &rank.uuid);
+        let players = diff(&local_players, &players, |player| &player.uuid);
+
+        report.rankings = rankings.counts();
+        report.ranks = ranks.counts();
+        report.players = players.counts();
+        report.players.rejected = rejected_players.len();
+        report.rejected_players_uuids = rejected_players.into_iter().collect();
+        report.rejected_players_uuids.sort();
+
+        if report.is_dry_run {
+            return Ok(());
+        }
+
+        for ranking in &rankings.inserted {
+            sqlx::query!(
+                "INSERT INTO rankings_cache (uuid, name, created_at, archived_at) VALUES (?, ?, ?, ?)",
+                ranking.uuid,
+                ranking.name,
+                ranking.created_at,
+                ranking.archived_at,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        for ranking in &rankings.updated {
+            sqlx::query!(
+                "UPDATE rankings_cache SET name = ?, created_at = ?, archived_at = ?, deleted_at = NULL
+                WHERE uuid = ?",
+                ranking.name,
+                ranking.created_at,
+                ranking.archived_at,
+                ranking.uuid,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        for uuid in &rankings.deleted {
+            sqlx::query!("UPDATE rankings_cache SET deleted_at = ? WHERE uuid = ?", now, uuid)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        for rank in ranks.inserted.iter().chain(ranks.updated.iter()) {
+            sqlx::query!(
+                "INSERT INTO ranks_cache (uuid, ranking_uuid, name, required_points, required_exam, color, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ON CONFLICT (uuid) DO UPDATE SET
+                    ranking_uuid = ?2, name = ?3, required_points = ?4, required_exam = ?5,
+                    color = ?6, created_at = ?7, deleted_at = NULL",
+                rank.uuid,
+                rank.ranking_uuid,
+                rank.name,
+                rank.required_points,
+                rank.required_exam,
+                rank.color,
+                rank.created_at,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        for uuid in &ranks.deleted {
+            sqlx::query!("UPDATE ranks_cache SET deleted_at = ? WHERE uuid = ?", now, uuid)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        for player in players.inserted.iter().chain(players.updated.iter()) {
+            let search_terms = search::player_search_terms(
+                &player.usma_id,
+                player.nickname.as_deref(),
+                player.first_name.as_deref(),
+                player.last_name.as_deref(),
+                player.is_gdpr_agreed,
+            );
+            // free-text region is kept even when it does not match any known region
+            let region_code = player
+                .region
+                .as_deref()
+                .and_then(|region| geo::normalize_region(&player.country_code, region));
+
+            sqlx::query!(
+                "INSERT INTO players_cache (
+                    uuid, ranking_uuid, usma_id, first_name, last_name, city, region, country_code,
+                    nickname, is_exam_done, is_gdpr_agreed, is_guest, is_static, search_terms, created_at,
+                    region_code, ema_id
+                )
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, 0, ?12, ?13, ?14, ?15, ?16)
+                ON CONFLICT (uuid) DO UPDATE SET
+                    ranking_uuid = ?2, usma_id = ?3, first_name = ?4, last_name = ?5, city = ?6,
+                    region = ?7, country_code = ?8, nickname = ?9, is_exam_done = ?10,
+                    is_gdpr_agreed = ?11, is_static = ?12, search_terms = ?13, created_at = ?14,
+                    region_code = ?15, ema_id = ?16, deleted_at = NULL",
+                player.uuid,
+                player.ranking_uuid,
+                player.usma_id,
+                player.first_name,
+                player.last_name,
+                player.city,
+                player.region,
+                player.country_code,
+                player.nickname,
+                player.is_exam_done,
+                player.is_gdpr_agreed,
+                player.is_static,
+                search_terms,
+                player.created_at,
+                region_code,
+                player.ema_id,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        for uuid in &players.deleted {
+            sqlx::query!("UPDATE players_cache SET deleted_at = ? WHERE uuid = ?", now, uuid)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        // ranks and players decide content of the snapshot, rankings with changes are recomputed
+        let mut dirty_rankings = ranks
+            .inserted
+            .iter()
+            .chain(ranks.updated.iter())
+            .map(|rank| rank.ranking_uuid.clone())
+            .chain(
+                players
+                    .inserted
+                    .iter()
+                    .chain(players.updated.iter())
+                    .map(|player| player.ranking_uuid.clone()),
+            )
+            .chain(ranks.deleted.iter().filter_map(|uuid| {
+                local_ranks.get(uuid).map(|local| local.record.ranking_uuid.clone())
+            }))
+            .chain(players.deleted.iter().filter_map(|uuid| {
+                local_players.get(uuid).map(|local| local.record.ranking_uuid.clone())
+            }))
+            .collect::<Vec<_>>();
+        dirty_rankings.sort();
+        dirty_rankings.dedup();
+
+        for ranking_uuid in &dirty_rankings {
+            sqlx::query!(
+                "UPDATE rankings_cache SET computed_at = NULL WHERE uuid = ?",
+                ranking_uuid
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        if !dirty_rankings.is_empty() {
+            self.compute.request_recompute();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,467 @@
+use axum::{extract::Path, response::IntoResponse, routing::{get, post}, Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::Connection;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::{
+    app::AppError,
+    db::DatabaseConnection,
+    firebase,
+    tournaments::{self, Tournament, TournamentRules},
+    users,
+    validate::{ValidatedJson, ValidatedQuery},
+};
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/rankings/:ranking_uuid/teams",
+            get(teams_index).post(teams_create),
+        )
+        .route(
+            "/rankings/:ranking_uuid/teams/standings",
+            get(teams_standings),
+        )
+        .route(
+            "/rankings/:ranking_uuid/teams/:team_uuid",
+            get(teams_show),
+        )
+        .route(
+            "/rankings/:ranking_uuid/teams/:team_uuid/members",
+            post(teams_members_join),
+        )
+        .route(
+            "/rankings/:ranking_uuid/teams/:team_uuid/members/:player_uuid/leave",
+            post(teams_members_leave),
+        )
+}
+
+pub const TEAM_SIZE: i64 = 4;
+
+pub struct Team {
+    pub uuid: String,
+    pub ranking_uuid: String,
+    pub tournament_uuid: Option<String>,
+    pub creator_uid: String,
+}
+
+impl Team {
+    pub async fn fetch(
+        conn: &mut sqlx::SqliteConnection,
+        ranking_uuid: &str,
+        team_uuid: &str,
+    ) -> Result<Self, AppError> {
+        let row = sqlx::query!(
+            "SELECT uuid, ranking_uuid, tournament_uuid, creator_uid FROM teams WHERE ranking_uuid = ? AND uuid = ?",
+            ranking_uuid,
+            team_uuid,
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(AppError::TeamNotFound)?;
+
+        Ok(Self {
+            uuid: row.uuid,
+            ranking_uuid: row.ranking_uuid,
+            tournament_uuid: row.tournament_uuid,
+            creator_uid: row.creator_uid,
+        })
+    }
+
+    /// Creator of the team manages its members, admins can manage any team
+    pub fn authorize(
+        &self,
+        current_user: &users::CurrentUser,
+        current_admin: &Option<users::CurrentAdmin>,
+    ) -> Result<(), AppError> {
+        if current_admin.is_some() || current_user.user_uid == self.creator_uid {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden)
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TeamsIndex {
+    // teams of the tournament, teams of the ranking when not given
+    #[validate(length(equal = 36))]
+    tournament_uuid: Option<String>,
+}
+
+pub async fn teams_index(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    ValidatedQuery(input): ValidatedQuery<TeamsIndex>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let data = sqlx::query!(
+        r#"SELECT
+            t.uuid, t.tournament_uuid, t.name, t.created_at,
+            (SELECT COUNT(*) FROM team_members tm
+                WHERE tm.team_uuid = t.uuid AND tm.left_at IS NULL) as "members_count!: i64"
+        FROM teams t
+        WHERE t.ranking_uuid = ? AND t.tournament_uuid IS ?
+        ORDER BY t.name ASC"#,
+        ranking_uuid,
+        input.tournament_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": data.iter().map(|row| {
+            json!({
+                "uuid": row.uuid,
+                "tournament_uuid": row.tournament_uuid,
+                "name": row.name,
+                "members_count": row.members_count,
+                "created_at": row.created_at,
+            })
+        }).collect::<Vec<_>>(),
+        "count": data.len(),
+    })))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TeamsCreate {
+    #[validate(length(min = 1, max = 64))]
+    name: String,
+    // only organiser of the tournament creates its teams
+    #[validate(length(equal = 36))]
+    tournament_uuid: Option<String>,
+}
+
+/// Creates team of the tournament or of the ranking, names are unique among competing teams
+pub async fn teams_create(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path(ranking_uuid): Path<String>,
+    ValidatedJson(input): ValidatedJson<TeamsCreate>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+    let name = input.name.trim();
+
+    if let Some(tournament_uuid) = &input.tournament_uuid {
+        Tournament::fetch(&mut conn, &ranking_uuid, tournament_uuid)
+            .await?
+            .authorize(&current_user, &current_admin)?;
+    } else {
+        sqlx::query_scalar!(
+            "SELECT 1 FROM rankings_cache WHERE uuid = ? AND deleted_at IS NULL",
+            ranking_uuid,
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        .ok_or(AppError::RankingNotFound)?;
+    }
+
+    let mut tx = conn.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO teams (uuid, ranking_uuid, tournament_uuid, name, creator_uid, created_at)
+        VALUES (?, ?, ?, ?, ?, strftime('%s', 'now'))",
+        uuid,
+        ranking_uuid,
+        input.tournament_uuid,
+        name,
+        current_user.user_uid,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let is_name_taken = sqlx::query_scalar!(
+        "SELECT 1 FROM teams WHERE ranking_uuid = ? AND tournament_uuid IS ? AND name = ? AND uuid != ?",
+        ranking_uuid,
+        input.tournament_uuid,
+        name,
+        uuid,
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .is_some();
+
+    if is_name_taken {
+        return Err(AppError::TeamNameConflict);
+    }
+
+    tx.commit().await?;
+
+    team_json(&mut conn, &ranking_uuid, &uuid).await
+}
+
+pub async fn teams_show(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path((ranking_uuid, team_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    team_json(&mut conn, &ranking_uuid, &team_uuid).await
+}
+
+/// Team with current and past members
+async fn team_json(
+    conn: &mut sqlx::SqliteConnection,
+    ranking_uuid: &str,
+    team_uuid: &str,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let team = sqlx::query!(
+        "SELECT uuid, tournament_uuid, name, created_at FROM teams WHERE ranking_uuid = ? AND uuid = ?",
+        ranking_uuid,
+        team_uuid,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::TeamNotFound)?;
+
+    let members = sqlx::query!(
+        "SELECT tm.player_uuid, p.nickname, tm.joined_at, tm.left_at
+        FROM team_members tm
+        LEFT JOIN players_cache p ON p.uuid = tm.player_uuid
+        WHERE tm.team_uuid = ?
+        ORDER BY tm.joined_at ASC",
+        team_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "uuid": team.uuid,
+                "tournament_uuid": team.tournament_uuid,
+                "name": team.name,
+                "created_at": team.created_at,
+                "$members": json!({
+                    "items": members.iter().map(|row| {
+                        json!({
+                            "player_uuid": row.player_uuid,
+                            "nickname": row.nickname,
+                            "joined_at": row.joined_at,
+                            "left_at": row.left_at,
+                        })
+                    }).collect::<Vec<_>>(),
+                    "count": members.len(),
+                }),
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TeamsMembersJoin {
+    #[validate(length(equal = 36))]
+    player_uuid: String,
+    // games ended since then count toward the team, now when not given
+    joined_at: Option<i64>,
+}
+
+/// Adds current member, team has at most four of them and a player can be current member
+/// of only one team competing in the same tournament or ranking. Membership can't start
+/// before the player left a team of the same tournament or ranking, so no game counts twice
+pub async fn teams_members_join(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, team_uuid)): Path<(String, String)>,
+    ValidatedJson(input): ValidatedJson<TeamsMembersJoin>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let team = Team::fetch(&mut conn, &ranking_uuid, &team_uuid).await?;
+
+    team.authorize(&current_user, &current_admin)?;
+
+    // players of tournament teams must be registered in the tournament
+    sqlx::query_scalar!(
+        "SELECT 1 FROM players_cache p
+        WHERE p.ranking_uuid = ?1 AND p.uuid = ?2 AND p.deleted_at IS NULL
+        AND (?3 IS NULL OR EXISTS(
+            SELECT 1 FROM tournament_players tp WHERE tp.tournament_uuid = ?3 AND tp.player_uuid = p.uuid
+        ))",
+        team.ranking_uuid,
+        input.player_uuid,
+        team.tournament_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::PlayerNotFound)?;
+
+    if let Some(joined_at) = input.joined_at {
+        let last_left_at = sqlx::query_scalar!(
+            r#"SELECT MAX(tm.left_at) as "left_at: i64"
+            FROM team_members tm
+            INNER JOIN teams t ON t.uuid = tm.team_uuid
+            WHERE t.ranking_uuid = ? AND t.tournament_uuid IS ? AND tm.player_uuid = ?"#,
+            team.ranking_uuid,
+            team.tournament_uuid,
+            input.player_uuid,
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        if last_left_at.is_some_and(|left_at| joined_at < left_at) {
+            let mut errors = ValidationErrors::new();
+            errors.add("joined_at", ValidationError::new("player was a team member since then"));
+
+            return Err(AppError::ValidationError(errors));
+        }
+    }
+
+    let mut tx = conn.begin().await?;
+
+    // write first so concurrent requests can't overfill the team
+    sqlx::query!(
+        "INSERT INTO team_members (team_uuid, player_uuid, joined_at)
+        VALUES (?, ?, COALESCE(?, strftime('%s', 'now')))",
+        team.uuid,
+        input.player_uuid,
+        input.joined_at,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let memberships = sqlx::query!(
+        r#"SELECT tm.team_uuid, COUNT(*) as "count!: i64"
+        FROM team_members tm
+        INNER JOIN teams t ON t.uuid = tm.team_uuid
+        WHERE t.ranking_uuid = ? AND t.tournament_uuid IS ? AND tm.player_uuid = ? AND tm.left_at IS NULL
+        GROUP BY tm.team_uuid"#,
+        team.ranking_uuid,
+        team.tournament_uuid,
+        input.player_uuid,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    if memberships.len() > 1 || memberships.iter().any(|row| row.count > 1) {
+        return Err(AppError::TeamPlayerAlreadyMember);
+    }
+
+    let members_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM team_members WHERE team_uuid = ? AND left_at IS NULL"#,
+        team.uuid,
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    if members_count > TEAM_SIZE {
+        return Err(AppError::TeamFull);
+    }
+
+    tx.commit().await?;
+
+    team_json(&mut conn, &ranking_uuid, &team_uuid).await
+}
+
+/// Ends current membership, games ended since then don't count toward the team
+pub async fn teams_members_leave(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, team_uuid, player_uuid)): Path<(String, String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let team = Team::fetch(&mut conn, &ranking_uuid, &team_uuid).await?;
+
+    team.authorize(&current_user, &current_admin)?;
+
+    sqlx::query!(
+        "UPDATE team_members SET left_at = strftime('%s', 'now')
+        WHERE team_uuid = ? AND player_uuid = ? AND left_at IS NULL",
+        team.uuid,
+        player_uuid,
+    )
+    .execute(&mut conn)
+    .await?
+    .rows_affected()
+    .eq(&1)
+    .then_some(())
+    .ok_or(AppError::PlayerNotFound)?;
+
+    team_json(&mut conn, &ranking_uuid, &team_uuid).await
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TeamsStandings {
+    // standings of teams of the tournament from its games, of ranking teams when not given
+    #[validate(length(equal = 36))]
+    tournament_uuid: Option<String>,
+}
+
+/// Team standings summed from results of members in games ended while they were members,
+/// scored by rules of the tournament or by default tournament rules for ranking teams
+pub async fn teams_standings(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    ValidatedQuery(input): ValidatedQuery<TeamsStandings>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let rules = match &input.tournament_uuid {
+        Some(tournament_uuid) => Tournament::fetch(&mut conn, &ranking_uuid, tournament_uuid).await?.rules,
+        None => TournamentRules {
+            return_points: tournaments::DEFAULT_RETURN_POINTS,
+            uma: tournaments::DEFAULT_UMA,
+            tie_breakers: tournaments::DEFAULT_TIE_BREAKERS.to_vec(),
+        },
+    };
+
+    let teams = sqlx::query!(
+        "SELECT uuid, name FROM teams WHERE ranking_uuid = ? AND tournament_uuid IS ?",
+        ranking_uuid,
+        input.tournament_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let results = sqlx::query!(
+        "SELECT tm.team_uuid, r.points, r.placement
+        FROM game_session_results_cache r
+        INNER JOIN game_sessions gs ON gs.uuid = r.game_session_uuid
+        INNER JOIN team_members tm ON tm.player_uuid = r.player_uuid
+            AND r.ended_at >= tm.joined_at AND (tm.left_at IS NULL OR r.ended_at < tm.left_at)
+        INNER JOIN teams t ON t.uuid = tm.team_uuid
+        WHERE t.ranking_uuid = ?1 AND r.ranking_uuid = ?1 AND t.tournament_uuid IS ?2
+        AND (?2 IS NULL OR gs.tournament_uuid = ?2)",
+        ranking_uuid,
+        input.tournament_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?
+    .into_iter()
+    .map(|row| (row.team_uuid, row.points, row.placement))
+    .collect::<Vec<_>>();
+
+    let uuids = teams.iter().map(|row| row.uuid.clone()).collect::<Vec<_>>();
+    let standings = tournaments::standings(&rules, &uuids, &results);
+
+    Ok(Json(json!({
+        "items": standings.iter().map(|standing| {
+            json!({
+                "position": standing.position,
+                "team_uuid": standing.uuid,
+                "name": teams.iter().find(|row| row.uuid == standing.uuid).map(|row| &row.name),
+                "games_count": standing.games,
+                "points": standing.tournament_points,
+                "total_points": standing.total_points,
+                "placements": standing.placements,
+            })
+        }).collect::<Vec<_>>(),
+        "count": standings.len(),
+        "tie_breakers": rules.tie_breakers,
+    })))
+}
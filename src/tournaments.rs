@@ -0,0 +1,1439 @@
+use std::cmp::Ordering;
+
+use axum::{
+    extract::Path,
+    http::header,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use hashbrown::HashMap;
+use rand::prelude::SliceRandom;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::Connection;
+use validator::{Validate, ValidationError};
+
+use crate::{
+    app::AppError,
+    db::DatabaseConnection,
+    firebase, gdpr, geo, scoring, search, seating, users,
+    validate::ValidatedJson,
+};
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/rankings/:ranking_uuid/tournaments",
+            get(tournaments_index).post(tournaments_create),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid",
+            get(tournaments_show),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/players",
+            post(tournaments_players_register),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/players/:player_uuid/check_in",
+            post(tournaments_players_check_in),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/players/:player_uuid/withdraw",
+            post(tournaments_players_withdraw),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/rounds",
+            post(tournaments_rounds_create),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/rounds/seating",
+            post(tournaments_rounds_seating),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/rounds/:round_number/game_sessions",
+            post(tournaments_rounds_link),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/finish",
+            post(tournaments_finish),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/standings",
+            get(tournaments_standings),
+        )
+        .route(
+            "/rankings/:ranking_uuid/tournaments/:tournament_uuid/export/ema",
+            get(tournaments_export_ema),
+        )
+}
+
+pub const TOURNAMENT_STATUS_REGISTRATION: &str = "registration";
+pub const TOURNAMENT_STATUS_IN_PROGRESS: &str = "in_progress";
+pub const TOURNAMENT_STATUS_FINISHED: &str = "finished";
+
+// without oka, so tournament points of every game sum up to zero
+pub const DEFAULT_RETURN_POINTS: i64 = scoring::STARTING_POINTS;
+pub const DEFAULT_UMA: [i64; 4] = [15000, 5000, -5000, -15000];
+pub const DEFAULT_TIE_BREAKERS: &[TieBreaker] = &[TieBreaker::TotalPoints, TieBreaker::FirstPlaces];
+
+/// Decides order of players with equal tournament points, applied in configured order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreaker {
+    /// sum of final scores
+    TotalPoints,
+    /// more first places, then more second places and so on
+    FirstPlaces,
+    /// tournament points of the best game
+    BestGame,
+    AveragePlacement,
+}
+
+/// Scoring of the tournament, tournament points of a game are
+/// final score minus return points plus uma of the placement
+#[derive(Debug, Clone)]
+pub struct TournamentRules {
+    pub return_points: i64,
+    pub uma: [i64; 4],
+    pub tie_breakers: Vec<TieBreaker>,
+}
+
+impl TournamentRules {
+    /// Tournament points of a single game
+    pub fn game_points(&self, points: i64, placement: i64) -> i64 {
+        points - self.return_points + self.uma[(placement.clamp(1, 4) - 1) as usize]
+    }
+}
+
+pub struct Tournament {
+    pub uuid: String,
+    pub ranking_uuid: String,
+    pub place_uuid: Option<String>,
+    pub status: String,
+    pub rounds_count: i64,
+    pub creator_uid: String,
+    pub rules: TournamentRules,
+}
+
+impl Tournament {
+    pub async fn fetch(
+        conn: &mut sqlx::SqliteConnection,
+        ranking_uuid: &str,
+        tournament_uuid: &str,
+    ) -> Result<Self, AppError> {
+        let row = sqlx::query!(
+            "SELECT uuid, ranking_uuid, place_uuid, status, rounds_count, return_points, uma, tie_breakers, creator_uid
+            FROM tournaments WHERE ranking_uuid = ? AND uuid = ?",
+            ranking_uuid,
+            tournament_uuid,
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(AppError::TournamentNotFound)?;
+
+        Ok(Self {
+            uuid: row.uuid,
+            ranking_uuid: row.ranking_uuid,
+            place_uuid: row.place_uuid,
+            status: row.status,
+            rounds_count: row.rounds_count,
+            creator_uid: row.creator_uid,
+            rules: TournamentRules {
+                return_points: row.return_points,
+                uma: serde_json::from_str(&row.uma)?,
+                tie_breakers: serde_json::from_str(&row.tie_breakers)?,
+            },
+        })
+    }
+
+    /// Organiser is the user who created the tournament, admins can manage any tournament
+    pub fn authorize(
+        &self,
+        current_user: &users::CurrentUser,
+        current_admin: &Option<users::CurrentAdmin>,
+    ) -> Result<(), AppError> {
+        if current_admin.is_some() || current_user.user_uid == self.creator_uid {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden)
+        }
+    }
+
+    /// Players can manage their own registration, organiser anyone's
+    fn authorize_player(
+        &self,
+        player_uuid: &str,
+        current_user: &users::CurrentUser,
+        current_admin: &Option<users::CurrentAdmin>,
+    ) -> Result<(), AppError> {
+        if current_user.player_uuid == player_uuid {
+            Ok(())
+        } else {
+            self.authorize(current_user, current_admin)
+        }
+    }
+
+    fn ensure_not_finished(&self) -> Result<(), AppError> {
+        if self.status == TOURNAMENT_STATUS_FINISHED {
+            Err(AppError::TournamentFinished)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Standing of a player, or of a league entrant, accumulated over linked game sessions
+#[derive(Debug, Default, Clone)]
+pub struct Standing {
+    pub uuid: String,
+    pub games: i64,
+    pub tournament_points: i64,
+    pub total_points: i64,
+    pub placements: [i64; 4],
+    pub best_game: Option<i64>,
+    /// 1-based, players equal under every tie-breaker share the position
+    pub position: usize,
+}
+
+impl Standing {
+    fn add_game(&mut self, rules: &TournamentRules, points: i64, placement: i64) {
+        let index = (placement.clamp(1, 4) - 1) as usize;
+        let tournament_points = rules.game_points(points, placement);
+
+        self.games += 1;
+        self.tournament_points += tournament_points;
+        self.total_points += points;
+        self.placements[index] += 1;
+        self.best_game = Some(self.best_game.map_or(tournament_points, |best| best.max(tournament_points)));
+    }
+
+    fn placement_sum(&self) -> i64 {
+        self.placements
+            .iter()
+            .enumerate()
+            .map(|(index, count)| (index as i64 + 1) * count)
+            .sum()
+    }
+
+    /// `Less` when `self` stands higher than `other`, players without games are always last
+    /// as their zero points would otherwise rank them above players with negative points
+    fn compare(&self, other: &Self, tie_breakers: &[TieBreaker]) -> Ordering {
+        let ordering = (self.games == 0)
+            .cmp(&(other.games == 0))
+            .then_with(|| other.tournament_points.cmp(&self.tournament_points));
+
+        tie_breakers.iter().fold(
+            ordering,
+            |ordering, tie_breaker| {
+                ordering.then_with(|| match tie_breaker {
+                    TieBreaker::TotalPoints => other.total_points.cmp(&self.total_points),
+                    TieBreaker::FirstPlaces => other.placements.cmp(&self.placements),
+                    TieBreaker::BestGame => other.best_game.cmp(&self.best_game),
+                    // averages are compared without division
+                    TieBreaker::AveragePlacement => match (self.games, other.games) {
+                        (0, 0) => Ordering::Equal,
+                        (0, _) => Ordering::Greater,
+                        (_, 0) => Ordering::Less,
+                        (games, other_games) => {
+                            (self.placement_sum() * other_games).cmp(&(other.placement_sum() * games))
+                        }
+                    },
+                })
+            },
+        )
+    }
+}
+
+/// Standings of registered players, substitutes and other players of linked games are left out,
+/// `results` are player uuid, final score and placement of every linked game.
+/// League tables are ranked the same way with results keyed by entrant uuid
+pub fn standings(rules: &TournamentRules, players: &[String], results: &[(String, i64, i64)]) -> Vec<Standing> {
+    let mut standings = players
+        .iter()
+        .map(|player_uuid| {
+            (player_uuid.clone(), Standing {
+                uuid: player_uuid.clone(),
+                ..Default::default()
+            })
+        })
+        .collect::<HashMap<_, _>>();
+
+    for (player_uuid, points, placement) in results {
+        if let Some(standing) = standings.get_mut(player_uuid) {
+            standing.add_game(rules, *points, *placement);
+        }
+    }
+
+    let mut standings = standings.into_values().collect::<Vec<_>>();
+    standings.sort_by(|a, b| {
+        a.compare(b, &rules.tie_breakers)
+            .then_with(|| a.uuid.cmp(&b.uuid))
+    });
+
+    for index in 0..standings.len() {
+        standings[index].position = match index {
+            0 => 1,
+            _ if standings[index].compare(&standings[index - 1], &rules.tie_breakers) == Ordering::Equal => {
+                standings[index - 1].position
+            }
+            _ => index + 1,
+        };
+    }
+
+    standings
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_tournaments_create_input"))]
+pub struct TournamentsCreate {
+    #[validate(length(min = 1, max = 64))]
+    name: String,
+    #[validate(length(equal = 36))]
+    place_uuid: Option<String>,
+    #[validate(range(min = 1, max = 20))]
+    rounds_count: i64,
+    starts_at: Option<i64>,
+    #[validate(range(min = 0, max = 100000))]
+    return_points: Option<i64>,
+    #[validate(custom = "validate_uma")]
+    uma: Option<Vec<i64>>,
+    tie_breakers: Option<Vec<TieBreaker>>,
+}
+
+/// Uma of four placements which doesn't create or remove points
+pub fn validate_uma(uma: &[i64]) -> Result<(), ValidationError> {
+    if uma.len() == 4 && uma.iter().sum::<i64>() == 0 {
+        Ok(())
+    } else {
+        Err(ValidationError::new("uma must have four values summing up to zero"))
+    }
+}
+
+fn validate_tournaments_create_input(input: &TournamentsCreate) -> Result<(), ValidationError> {
+    let is_tie_breakers_valid = input.tie_breakers.as_ref().is_none_or(|tie_breakers| {
+        tie_breakers.len() <= 4
+            && tie_breakers
+                .iter()
+                .enumerate()
+                .all(|(index, tie_breaker)| !tie_breakers[..index].contains(tie_breaker))
+    });
+
+    if is_tie_breakers_valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("tie breakers must be distinct"))
+    }
+}
+
+pub async fn tournaments_create(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    ValidatedJson(input): ValidatedJson<TournamentsCreate>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+    let return_points = input.return_points.unwrap_or(DEFAULT_RETURN_POINTS);
+    let uma = serde_json::to_string(&input.uma.unwrap_or_else(|| DEFAULT_UMA.to_vec()))?;
+    let tie_breakers = serde_json::to_string(
+        &input
+            .tie_breakers
+            .unwrap_or_else(|| DEFAULT_TIE_BREAKERS.to_vec()),
+    )?;
+
+    sqlx::query_scalar!(
+        "SELECT 1 FROM rankings_cache WHERE uuid = ? AND deleted_at IS NULL",
+        ranking_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::RankingNotFound)?;
+
+    if let Some(place_uuid) = &input.place_uuid {
+        sqlx::query_scalar!("SELECT 1 FROM places WHERE uuid = ?", place_uuid)
+            .fetch_optional(&mut conn)
+            .await?
+            .ok_or(AppError::PlaceNotFound)?;
+    }
+
+    sqlx::query!(
+        "INSERT INTO tournaments (
+            uuid, ranking_uuid, place_uuid, name, status, rounds_count, return_points, uma,
+            tie_breakers, creator_uid, starts_at, created_at
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))",
+        uuid,
+        ranking_uuid,
+        input.place_uuid,
+        input.name,
+        TOURNAMENT_STATUS_REGISTRATION,
+        input.rounds_count,
+        return_points,
+        uma,
+        tie_breakers,
+        current_user.user_uid,
+        input.starts_at,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    tournament_json(&mut conn, &ranking_uuid, &uuid).await
+}
+
+pub async fn tournaments_index(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path(ranking_uuid): Path<String>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    let data = sqlx::query!(
+        r#"SELECT
+            t.uuid, t.place_uuid, t.name, t.status, t.rounds_count, t.starts_at, t.finished_at, t.created_at,
+            (SELECT COUNT(*) FROM tournament_players p WHERE p.tournament_uuid = t.uuid) as "players_count!: i64"
+        FROM tournaments t
+        WHERE t.ranking_uuid = ?
+        ORDER BY t.created_at DESC
+        LIMIT 50"#,
+        ranking_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": data.iter().map(|row| {
+            json!({
+                "uuid": row.uuid,
+                "place_uuid": row.place_uuid,
+                "name": row.name,
+                "status": row.status,
+                "rounds_count": row.rounds_count,
+                "players_count": row.players_count,
+                "starts_at": row.starts_at,
+                "finished_at": row.finished_at,
+                "created_at": row.created_at,
+            })
+        }).collect::<Vec<_>>(),
+        "count": data.len(),
+    })))
+}
+
+pub async fn tournaments_show(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path((ranking_uuid, tournament_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+
+    tournament_json(&mut conn, &ranking_uuid, &tournament_uuid).await
+}
+
+/// Tournament with registered players and rounds with linked game sessions
+async fn tournament_json(
+    conn: &mut sqlx::SqliteConnection,
+    ranking_uuid: &str,
+    tournament_uuid: &str,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let tournament = sqlx::query!(
+        "SELECT
+            uuid, place_uuid, name, status, rounds_count, return_points, uma, tie_breakers,
+            starts_at, finished_at, created_at
+        FROM tournaments WHERE ranking_uuid = ? AND uuid = ?",
+        ranking_uuid,
+        tournament_uuid,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::TournamentNotFound)?;
+
+    let players = sqlx::query!(
+        "SELECT tp.player_uuid, p.nickname, tp.checked_in_at, tp.created_at
+        FROM tournament_players tp
+        LEFT JOIN players_cache p ON p.uuid = tp.player_uuid
+        WHERE tp.tournament_uuid = ?
+        ORDER BY tp.created_at ASC",
+        tournament_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let rounds = sqlx::query!(
+        "SELECT uuid, number, created_at FROM tournament_rounds
+        WHERE tournament_uuid = ?
+        ORDER BY number ASC",
+        tournament_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let game_sessions = sqlx::query!(
+        r#"SELECT uuid, tournament_round_uuid as "tournament_round_uuid!",
+            player1_uuid, player2_uuid, player3_uuid, player4_uuid
+        FROM game_sessions
+        WHERE tournament_uuid = ? AND tournament_round_uuid IS NOT NULL
+        ORDER BY created_at ASC"#,
+        tournament_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let byes = sqlx::query!(
+        "SELECT tournament_round_uuid, player_uuid FROM tournament_byes
+        WHERE tournament_uuid = ?
+        ORDER BY created_at ASC",
+        tournament_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let substitutes = sqlx::query_scalar!(
+        "SELECT player_uuid FROM tournament_substitutes
+        WHERE tournament_uuid = ?
+        ORDER BY created_at ASC",
+        tournament_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "uuid": tournament.uuid,
+                "place_uuid": tournament.place_uuid,
+                "name": tournament.name,
+                "status": tournament.status,
+                "rounds_count": tournament.rounds_count,
+                "return_points": tournament.return_points,
+                "uma": serde_json::from_str::<serde_json::Value>(&tournament.uma)?,
+                "tie_breakers": serde_json::from_str::<serde_json::Value>(&tournament.tie_breakers)?,
+                "starts_at": tournament.starts_at,
+                "finished_at": tournament.finished_at,
+                "created_at": tournament.created_at,
+                "$players": json!({
+                    "items": players.iter().map(|row| {
+                        json!({
+                            "player_uuid": row.player_uuid,
+                            "nickname": row.nickname,
+                            "checked_in_at": row.checked_in_at,
+                            "created_at": row.created_at,
+                        })
+                    }).collect::<Vec<_>>(),
+                    "count": players.len(),
+                }),
+                "substitutes_uuids": substitutes,
+                "$rounds": json!({
+                    "items": rounds.iter().map(|round| {
+                        let round_game_sessions = game_sessions
+                            .iter()
+                            .filter(|row| row.tournament_round_uuid == round.uuid)
+                            .collect::<Vec<_>>();
+                        let round_byes = byes
+                            .iter()
+                            .filter(|row| row.tournament_round_uuid == round.uuid)
+                            .map(|row| &row.player_uuid)
+                            .collect::<Vec<_>>();
+
+                        json!({
+                            "uuid": round.uuid,
+                            "number": round.number,
+                            "created_at": round.created_at,
+                            "byes_players_uuids": round_byes,
+                            "$game_sessions": json!({
+                                "items": round_game_sessions.iter().map(|row| {
+                                    json!({
+                                        "uuid": row.uuid,
+                                        "players_uuids": [
+                                            row.player1_uuid, row.player2_uuid, row.player3_uuid, row.player4_uuid,
+                                        ],
+                                    })
+                                }).collect::<Vec<_>>(),
+                                "count": round_game_sessions.len(),
+                            }),
+                        })
+                    }).collect::<Vec<_>>(),
+                    "count": rounds.len(),
+                }),
+            })
+        ],
+        "count": 1,
+    })))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TournamentsPlayersRegister {
+    // registers the current player when not given
+    #[validate(length(equal = 36))]
+    player_uuid: Option<String>,
+}
+
+/// Registers the player while the tournament is open for registration
+pub async fn tournaments_players_register(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, tournament_uuid)): Path<(String, String)>,
+    ValidatedJson(input): ValidatedJson<TournamentsPlayersRegister>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+    let player_uuid = input.player_uuid.unwrap_or_else(|| current_user.player_uuid.clone());
+
+    tournament.authorize_player(&player_uuid, &current_user, &current_admin)?;
+
+    if tournament.status != TOURNAMENT_STATUS_REGISTRATION {
+        return Err(AppError::TournamentRegistrationClosed);
+    }
+
+    sqlx::query_scalar!(
+        "SELECT 1 FROM players_cache WHERE ranking_uuid = ? AND uuid = ? AND deleted_at IS NULL",
+        tournament.ranking_uuid,
+        player_uuid,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::PlayerNotFound)?;
+
+    sqlx::query!(
+        "INSERT INTO tournament_players (tournament_uuid, player_uuid, created_at)
+        VALUES (?, ?, strftime('%s', 'now'))
+        ON CONFLICT (tournament_uuid, player_uuid) DO NOTHING",
+        tournament.uuid,
+        player_uuid,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    tournament_json(&mut conn, &ranking_uuid, &tournament_uuid).await
+}
+
+/// Confirms registered player is present, only checked-in players can be seated
+pub async fn tournaments_players_check_in(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, tournament_uuid, player_uuid)): Path<(String, String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+
+    tournament.authorize_player(&player_uuid, &current_user, &current_admin)?;
+    tournament.ensure_not_finished()?;
+
+    sqlx::query!(
+        "UPDATE tournament_players SET checked_in_at = COALESCE(checked_in_at, strftime('%s', 'now'))
+        WHERE tournament_uuid = ? AND player_uuid = ?",
+        tournament.uuid,
+        player_uuid,
+    )
+    .execute(&mut conn)
+    .await?
+    .rows_affected()
+    .eq(&1)
+    .then_some(())
+    .ok_or(AppError::PlayerNotFound)?;
+
+    tournament_json(&mut conn, &ranking_uuid, &tournament_uuid).await
+}
+
+/// Removes the player from the tournament before it starts
+pub async fn tournaments_players_withdraw(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, tournament_uuid, player_uuid)): Path<(String, String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+
+    tournament.authorize_player(&player_uuid, &current_user, &current_admin)?;
+
+    if tournament.status != TOURNAMENT_STATUS_REGISTRATION {
+        return Err(AppError::TournamentRegistrationClosed);
+    }
+
+    sqlx::query!(
+        "DELETE FROM tournament_players WHERE tournament_uuid = ? AND player_uuid = ?",
+        tournament.uuid,
+        player_uuid,
+    )
+    .execute(&mut conn)
+    .await?
+    .rows_affected()
+    .eq(&1)
+    .then_some(())
+    .ok_or(AppError::PlayerNotFound)?;
+
+    tournament_json(&mut conn, &ranking_uuid, &tournament_uuid).await
+}
+
+/// Creates the next round, the first one closes registration
+pub async fn tournaments_rounds_create(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, tournament_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+
+    tournament.authorize(&current_user, &current_admin)?;
+    tournament.ensure_not_finished()?;
+
+    create_round(&mut conn, &tournament).await?;
+
+    tournament_json(&mut conn, &ranking_uuid, &tournament_uuid).await
+}
+
+/// Next round of the tournament, returns its uuid and number
+pub async fn create_round(
+    conn: &mut sqlx::SqliteConnection,
+    tournament: &Tournament,
+) -> Result<(String, i64), AppError> {
+    let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+    let mut tx = conn.begin().await?;
+
+    // write first so concurrent requests can't create the same round twice
+    sqlx::query!(
+        "UPDATE tournaments SET status = ? WHERE uuid = ?",
+        TOURNAMENT_STATUS_IN_PROGRESS,
+        tournament.uuid,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let number = sqlx::query_scalar!(
+        r#"SELECT COALESCE(MAX(number), 0) + 1 as "number!: i64" FROM tournament_rounds WHERE tournament_uuid = ?"#,
+        tournament.uuid,
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    if number > tournament.rounds_count {
+        return Err(AppError::TournamentRoundsExceeded);
+    }
+
+    sqlx::query!(
+        "INSERT INTO tournament_rounds (uuid, tournament_uuid, number, created_at)
+        VALUES (?, ?, ?, strftime('%s', 'now'))",
+        uuid,
+        tournament.uuid,
+        number,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((uuid, number))
+}
+
+/// How checked-in players are split into tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatingMethod {
+    Random,
+    /// players of similar standing play together
+    Swiss,
+}
+
+/// What happens to players who don't fill a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatingRemainder {
+    /// players sit the round out, the ones with fewest byes so far
+    Byes,
+    /// guest players fill the last table
+    Substitutes,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TournamentsRoundsSeating {
+    // random for the first round and swiss for later rounds when not given
+    method: Option<SeatingMethod>,
+    remainder: Option<SeatingRemainder>,
+    // place of the tournament when not given
+    #[validate(length(equal = 36))]
+    place_uuid: Option<String>,
+}
+
+/// Creates the next round together with its game sessions, checked-in players are seated
+/// so they meet as few repeated opponents as possible and rotate through seat winds
+pub async fn tournaments_rounds_seating(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, tournament_uuid)): Path<(String, String)>,
+    ValidatedJson(input): ValidatedJson<TournamentsRoundsSeating>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+
+    tournament.authorize(&current_user, &current_admin)?;
+    tournament.ensure_not_finished()?;
+
+    let place_uuid = input
+        .place_uuid
+        .or_else(|| tournament.place_uuid.clone())
+        .ok_or(AppError::PlaceNotFound)?;
+
+    sqlx::query_scalar!("SELECT 1 FROM places WHERE uuid = ?", place_uuid)
+        .fetch_optional(&mut conn)
+        .await?
+        .ok_or(AppError::PlaceNotFound)?;
+
+    let mut players = sqlx::query_scalar!(
+        "SELECT player_uuid FROM tournament_players WHERE tournament_uuid = ? AND checked_in_at IS NOT NULL",
+        tournament.uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    if players.len() < 4 {
+        return Err(AppError::TournamentNotEnoughPlayers);
+    }
+
+    let rounds_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM tournament_rounds WHERE tournament_uuid = ?"#,
+        tournament.uuid,
+    )
+    .fetch_one(&mut conn)
+    .await?;
+
+    let method = input.method.unwrap_or(match rounds_count {
+        0 => SeatingMethod::Random,
+        _ => SeatingMethod::Swiss,
+    });
+
+    // shuffled first so players of equal standing don't always meet each other
+    players.shuffle(&mut rand::thread_rng());
+
+    if method == SeatingMethod::Swiss {
+        let positions = load_standings(&mut conn, &tournament)
+            .await?
+            .into_iter()
+            .map(|standing| (standing.uuid, standing.position))
+            .collect::<HashMap<_, _>>();
+
+        players.sort_by_key(|player_uuid| positions.get(player_uuid).copied().unwrap_or(usize::MAX));
+    }
+
+    let history = load_seating_history(&mut conn, &tournament).await?;
+    let byes = match input.remainder.unwrap_or(SeatingRemainder::Byes) {
+        SeatingRemainder::Byes => seating::pick_byes(&players, players.len() % 4, &history),
+        SeatingRemainder::Substitutes => vec![],
+    };
+
+    players.retain(|player_uuid| !byes.contains(player_uuid));
+
+    let mut tx = conn.begin().await?;
+    let (round_uuid, _) = create_round(&mut tx, &tournament).await?;
+
+    if players.len() % 4 != 0 {
+        let count = 4 - players.len() % 4;
+        let substitutes = take_substitutes(&mut tx, &tournament, &current_user.player_uuid, count).await?;
+
+        players.extend(substitutes);
+    }
+
+    for table in seating::seat(&players, &history, method == SeatingMethod::Swiss) {
+        let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+
+        sqlx::query!(
+            "INSERT INTO
+            game_sessions (
+                uuid, creator_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid,
+                tournament_uuid, tournament_round_uuid, place_uuid, is_shuffled, is_novice_friendly, is_unranked,
+                is_announced, is_player_certified_referee, is_league_game, ranking_uuid,
+                is_tonpuu, is_too_slow, is_tenant_host, is_hidden, is_not_computed,
+                is_verification_required, is_compute_skipped, created_at
+            )
+            VALUES (
+                ?, ?, ?, ?, ?, ?,
+                ?, ?, ?, 0, 0, 0,
+                0, 0, 0, ?,
+                0, 0, 0, 0, 1,
+                0, 0, strftime('%s', 'now')
+            )",
+            uuid,
+            current_user.player_uuid,
+            table[0],
+            table[1],
+            table[2],
+            table[3],
+            tournament.uuid,
+            round_uuid,
+            place_uuid,
+            tournament.ranking_uuid,
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    for player_uuid in &byes {
+        sqlx::query!(
+            "INSERT INTO tournament_byes (tournament_uuid, tournament_round_uuid, player_uuid, created_at)
+            VALUES (?, ?, ?, strftime('%s', 'now'))",
+            tournament.uuid,
+            round_uuid,
+            player_uuid,
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    tournament_json(&mut conn, &ranking_uuid, &tournament_uuid).await
+}
+
+/// Opponents, seats and byes of players in previous rounds and current teammates
+async fn load_seating_history(
+    conn: &mut sqlx::SqliteConnection,
+    tournament: &Tournament,
+) -> Result<seating::SeatingHistory, AppError> {
+    let mut history = seating::SeatingHistory::default();
+
+    let game_sessions = sqlx::query!(
+        "SELECT player1_uuid, player2_uuid, player3_uuid, player4_uuid FROM game_sessions
+        WHERE tournament_uuid = ? AND tournament_round_uuid IS NOT NULL",
+        tournament.uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for row in game_sessions {
+        history.add_table(&[row.player1_uuid, row.player2_uuid, row.player3_uuid, row.player4_uuid]);
+    }
+
+    let byes = sqlx::query_scalar!(
+        "SELECT player_uuid FROM tournament_byes WHERE tournament_uuid = ?",
+        tournament.uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for player_uuid in byes {
+        history.add_bye(&player_uuid);
+    }
+
+    let members = sqlx::query!(
+        "SELECT tm.team_uuid, tm.player_uuid FROM team_members tm
+        INNER JOIN teams t ON t.uuid = tm.team_uuid
+        WHERE t.tournament_uuid = ? AND tm.left_at IS NULL",
+        tournament.uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut teams = HashMap::<String, Vec<String>>::new();
+
+    for row in members {
+        teams.entry(row.team_uuid).or_default().push(row.player_uuid);
+    }
+
+    for players in teams.values() {
+        history.add_team(players);
+    }
+
+    Ok(history)
+}
+
+/// Substitutes of the tournament, guest players are created when there are not enough of them
+async fn take_substitutes(
+    conn: &mut sqlx::SqliteConnection,
+    tournament: &Tournament,
+    creator_player_uuid: &str,
+    count: usize,
+) -> Result<Vec<String>, AppError> {
+    let limit = count as i64;
+    let mut substitutes = sqlx::query_scalar!(
+        r#"SELECT player_uuid as "player_uuid!" FROM tournament_substitutes
+        WHERE tournament_uuid = ?
+        ORDER BY rowid
+        LIMIT ?"#,
+        tournament.uuid,
+        limit,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    if substitutes.len() >= count {
+        return Ok(substitutes);
+    }
+
+    // substitutes are from the same country as the organiser, organisers without a player
+    // (i.e. admins) fall back to the place of the tournament and then to its players
+    let country_code = sqlx::query_scalar!(
+        r#"SELECT COALESCE(
+            (SELECT country_code FROM players_cache WHERE uuid = ?1 AND deleted_at IS NULL),
+            (SELECT country_code FROM places WHERE uuid = ?2),
+            (SELECT p.country_code FROM tournament_players tp
+                INNER JOIN players_cache p ON p.uuid = tp.player_uuid
+                WHERE tp.tournament_uuid = ?3 ORDER BY tp.created_at LIMIT 1)
+        ) as "country_code?: String""#,
+        creator_player_uuid,
+        tournament.place_uuid,
+        tournament.uuid,
+    )
+    .fetch_one(&mut *conn)
+    .await?
+    .ok_or(AppError::TournamentNotEnoughPlayers)?;
+
+    while substitutes.len() < count {
+        let player_uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
+        let nickname = format!("Substitute {}", substitutes.len() + 1);
+        let search_terms = search::player_search_terms("", Some(&nickname), None, None, false);
+
+        sqlx::query!(
+            "INSERT INTO players_cache (
+                uuid, ranking_uuid, usma_id, first_name, last_name, city, region, country_code,
+                nickname, is_exam_done, is_gdpr_agreed, is_guest, is_static, search_terms, created_at
+            )
+            VALUES (?, ?, '', NULL, NULL, NULL, NULL, ?, ?, 0, 0, 1, 0, ?, strftime('%s', 'now'))",
+            player_uuid,
+            tournament.ranking_uuid,
+            country_code,
+            nickname,
+            search_terms,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO tournament_substitutes (tournament_uuid, player_uuid, created_at)
+            VALUES (?, ?, strftime('%s', 'now'))",
+            tournament.uuid,
+            player_uuid,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        substitutes.push(player_uuid);
+    }
+
+    Ok(substitutes)
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TournamentsRoundsLink {
+    #[validate(length(equal = 36))]
+    game_session_uuid: String,
+}
+
+/// Links existing game session to the round, every player of the session must be checked in
+/// and can't play in another session of the round
+pub async fn tournaments_rounds_link(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, tournament_uuid, round_number)): Path<(String, String, i64)>,
+    ValidatedJson(input): ValidatedJson<TournamentsRoundsLink>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+
+    tournament.authorize(&current_user, &current_admin)?;
+    tournament.ensure_not_finished()?;
+
+    let round_uuid = sqlx::query_scalar!(
+        "SELECT uuid FROM tournament_rounds WHERE tournament_uuid = ? AND number = ?",
+        tournament.uuid,
+        round_number,
+    )
+    .fetch_optional(&mut conn)
+    .await?
+    .ok_or(AppError::TournamentRoundNotFound)?;
+
+    let mut tx = conn.begin().await?;
+
+    let game_session = sqlx::query!(
+        "SELECT tournament_uuid, player1_uuid, player2_uuid, player3_uuid, player4_uuid
+        FROM game_sessions WHERE ranking_uuid = ? AND uuid = ?",
+        ranking_uuid,
+        input.game_session_uuid,
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or(AppError::GameSessionNotFound)?;
+
+    if game_session.tournament_uuid.is_some() {
+        return Err(AppError::GameSessionAlreadyLinked);
+    }
+
+    for player_uuid in [
+        &game_session.player1_uuid,
+        &game_session.player2_uuid,
+        &game_session.player3_uuid,
+        &game_session.player4_uuid,
+    ] {
+        sqlx::query_scalar!(
+            "SELECT 1 FROM tournament_players
+            WHERE tournament_uuid = ? AND player_uuid = ? AND checked_in_at IS NOT NULL",
+            tournament.uuid,
+            player_uuid,
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(AppError::TournamentPlayerNotCheckedIn)?;
+
+        let is_seated = sqlx::query_scalar!(
+            "SELECT 1 FROM game_sessions
+            WHERE tournament_round_uuid = ?1
+            AND ?2 IN (player1_uuid, player2_uuid, player3_uuid, player4_uuid)",
+            round_uuid,
+            player_uuid,
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .is_some();
+
+        if is_seated {
+            return Err(AppError::GameSessionAlreadyLinked);
+        }
+    }
+
+    sqlx::query!(
+        "UPDATE game_sessions SET tournament_uuid = ?, tournament_round_uuid = ? WHERE uuid = ?",
+        tournament.uuid,
+        round_uuid,
+        input.game_session_uuid,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    tournament_json(&mut conn, &ranking_uuid, &tournament_uuid).await
+}
+
+pub async fn tournaments_finish(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, tournament_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+
+    tournament.authorize(&current_user, &current_admin)?;
+    tournament.ensure_not_finished()?;
+
+    sqlx::query!(
+        "UPDATE tournaments SET status = ?, finished_at = strftime('%s', 'now') WHERE uuid = ?",
+        TOURNAMENT_STATUS_FINISHED,
+        tournament.uuid,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    tournament_json(&mut conn, &ranking_uuid, &tournament_uuid).await
+}
+
+/// Loads standings from final scores of computed game sessions linked to the tournament,
+/// games which are not ended or not computed yet are not counted
+pub async fn load_standings(
+    conn: &mut sqlx::SqliteConnection,
+    tournament: &Tournament,
+) -> Result<Vec<Standing>, AppError> {
+    let players = sqlx::query_scalar!(
+        "SELECT player_uuid FROM tournament_players WHERE tournament_uuid = ?",
+        tournament.uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let results = sqlx::query!(
+        "SELECT r.player_uuid, r.points, r.placement
+        FROM game_session_results_cache r
+        INNER JOIN game_sessions gs ON gs.uuid = r.game_session_uuid
+        WHERE gs.tournament_uuid = ? AND r.ranking_uuid = ?",
+        tournament.uuid,
+        tournament.ranking_uuid,
+    )
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|row| (row.player_uuid, row.points, row.placement))
+    .collect::<Vec<_>>();
+
+    Ok(standings(&tournament.rules, &players, &results))
+}
+
+pub async fn tournaments_standings(
+    _claims: firebase::FirebaseClaims,
+    _current_user: users::CurrentUser,
+    Path((ranking_uuid, tournament_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+    let standings = load_standings(&mut conn, &tournament).await?;
+
+    let nicknames = sqlx::query!(
+        "SELECT p.uuid, p.nickname FROM players_cache p
+        WHERE p.uuid IN (SELECT player_uuid FROM tournament_players WHERE tournament_uuid = ?)",
+        tournament.uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?
+    .into_iter()
+    .map(|row| (row.uuid, row.nickname))
+    .collect::<HashMap<_, _>>();
+
+    Ok(Json(json!({
+        "items": standings.iter().map(|standing| {
+            json!({
+                "position": standing.position,
+                "player_uuid": standing.uuid,
+                "nickname": nicknames.get(&standing.uuid),
+                "games_count": standing.games,
+                "tournament_points": standing.tournament_points,
+                "total_points": standing.total_points,
+                "placements": standing.placements,
+                "best_game_points": standing.best_game,
+            })
+        }).collect::<Vec<_>>(),
+        "count": standings.len(),
+        "tie_breakers": tournament.rules.tie_breakers,
+    })))
+}
+
+/// EMA ids are eight digits, the first two identify the national federation
+fn is_valid_ema_id(ema_id: &str) -> bool {
+    ema_id.len() == 8 && ema_id.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Points in thousands with one decimal, as in EMA results
+fn ema_points(points: i64) -> String {
+    format!("{:.1}", points as f64 / 1000.0)
+}
+
+/// Final standings as csv in the layout of EMA results, players who didn't play are left out
+/// and every other player must have valid EMA id. EMA ids are imported only from the federation
+/// export (see sync.rs), so players listed in the conflict have to be fixed there.
+/// Columns are position, last and first name, EMA id, nationality, tournament points,
+/// sum of final scores and tournament points of every round. Names of players who didn't agree
+/// to GDPR processing are left out, nickname is written as the last name instead
+pub async fn tournaments_export_ema(
+    _claims: firebase::FirebaseClaims,
+    current_user: users::CurrentUser,
+    current_admin: Option<users::CurrentAdmin>,
+    Path((ranking_uuid, tournament_uuid)): Path<(String, String)>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let tournament = Tournament::fetch(&mut conn, &ranking_uuid, &tournament_uuid).await?;
+
+    tournament.authorize(&current_user, &current_admin)?;
+
+    if tournament.status != TOURNAMENT_STATUS_FINISHED {
+        return Err(AppError::TournamentNotFinished);
+    }
+
+    // players without games are ranked last, so positions of the others have no gaps
+    let standings = load_standings(&mut conn, &tournament)
+        .await?
+        .into_iter()
+        .filter(|standing| standing.games > 0)
+        .collect::<Vec<_>>();
+
+    let players = sqlx::query!(
+        r#"SELECT
+            p.uuid, p.ema_id, p.first_name, p.last_name, p.nickname, p.country_code,
+            p.is_gdpr_agreed as "is_gdpr_agreed: bool"
+        FROM players_cache p
+        WHERE p.uuid IN (SELECT player_uuid FROM tournament_players WHERE tournament_uuid = ?)"#,
+        tournament.uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?
+    .into_iter()
+    .map(|row| (row.uuid.clone(), row))
+    .collect::<HashMap<_, _>>();
+
+    let missing = standings
+        .iter()
+        .filter(|standing| {
+            players
+                .get(&standing.uuid)
+                .and_then(|player| player.ema_id.as_deref())
+                .is_none_or(|ema_id| !is_valid_ema_id(ema_id))
+        })
+        .map(|standing| standing.uuid.clone())
+        .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+        return Err(AppError::TournamentPlayersMissingEmaId(missing));
+    }
+
+    let rounds = sqlx::query_scalar!(
+        "SELECT number FROM tournament_rounds WHERE tournament_uuid = ? ORDER BY number ASC",
+        tournament.uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let mut round_points = HashMap::<(String, i64), i64>::new();
+
+    for row in sqlx::query!(
+        "SELECT tr.number, r.player_uuid, r.points, r.placement
+        FROM game_session_results_cache r
+        INNER JOIN game_sessions gs ON gs.uuid = r.game_session_uuid
+        INNER JOIN tournament_rounds tr ON tr.uuid = gs.tournament_round_uuid
+        WHERE gs.tournament_uuid = ? AND r.ranking_uuid = ?",
+        tournament.uuid,
+        tournament.ranking_uuid,
+    )
+    .fetch_all(&mut conn)
+    .await?
+    {
+        *round_points.entry((row.player_uuid, row.number)).or_default() +=
+            tournament.rules.game_points(row.points, row.placement);
+    }
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let mut header = ["Position", "Last name", "First name", "EMA number", "Nationality", "Points", "Score"]
+        .iter()
+        .map(|column| column.to_string())
+        .collect::<Vec<_>>();
+    header.extend(rounds.iter().map(|number| format!("Round {}", number)));
+    writer.write_record(&header)?;
+
+    for standing in &standings {
+        // every player of standings has EMA id, so has the record
+        let player = &players[&standing.uuid];
+        let visible = gdpr::is_visible(player.is_gdpr_agreed, &player.uuid, &current_user);
+        let mut record = vec![
+            standing.position.to_string(),
+            gdpr::mask(visible, &player.last_name)
+                .or_else(|| player.nickname.clone())
+                .unwrap_or_default(),
+            gdpr::mask(visible, &player.first_name).unwrap_or_default(),
+            player.ema_id.clone().unwrap_or_default(),
+            geo::country_name(&player.country_code).unwrap_or(&player.country_code).to_string(),
+            ema_points(standing.tournament_points),
+            standing.total_points.to_string(),
+        ];
+        // rounds the player sat out are left empty
+        record.extend(rounds.iter().map(|number| {
+            round_points
+                .get(&(standing.uuid.clone(), *number))
+                .map_or_else(String::new, |points| ema_points(*points))
+        }));
+        writer.write_record(&record)?;
+    }
+
+    let body = writer
+        .into_inner()
+        .map_err(|err| AppError::Unknown(Some(err.into_error().into())))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"tournament-{}-ema.csv\"", tournament.uuid),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(tie_breakers: &[TieBreaker]) -> TournamentRules {
+        TournamentRules {
+            return_points: 30000,
+            uma: [0; 4],
+            tie_breakers: tie_breakers.to_vec(),
+        }
+    }
+
+    fn players(uuids: &[&str]) -> Vec<String> {
+        uuids.iter().map(|uuid| uuid.to_string()).collect()
+    }
+
+    fn result(uuid: &str, points: i64, placement: i64) -> (String, i64, i64) {
+        (uuid.to_string(), points, placement)
+    }
+
+    fn positions(standings: &[Standing]) -> Vec<(&str, usize)> {
+        standings
+            .iter()
+            .map(|standing| (standing.uuid.as_str(), standing.position))
+            .collect()
+    }
+
+    #[test]
+    fn game_points_adds_uma_to_points_over_return() {
+        let rules = TournamentRules {
+            return_points: 30000,
+            uma: DEFAULT_UMA,
+            tie_breakers: vec![],
+        };
+
+        assert_eq!(rules.game_points(45000, 1), 30000);
+        assert_eq!(rules.game_points(12000, 4), -33000);
+    }
+
+    #[test]
+    fn standings_by_first_places() {
+        let results = [
+            result("a", 40000, 1),
+            result("a", 20000, 4),
+            result("b", 30000, 2),
+            result("b", 30000, 3),
+        ];
+        let standings = standings(&rules(&[TieBreaker::FirstPlaces]), &players(&["b", "a"]), &results);
+
+        assert_eq!(positions(&standings), [("a", 1), ("b", 2)]);
+    }
+
+    #[test]
+    fn standings_by_average_placement() {
+        let results = [
+            result("a", 30000, 2),
+            result("b", 35000, 1),
+            result("b", 25000, 4),
+            result("c", 30000, 2),
+        ];
+        let standings = standings(&rules(&[TieBreaker::AveragePlacement]), &players(&["b", "c", "a"]), &results);
+
+        assert_eq!(positions(&standings), [("a", 1), ("c", 1), ("b", 3)]);
+    }
+
+    #[test]
+    fn standings_rank_players_without_games_last() {
+        let results = [result("a", 10000, 4), result("b", 50000, 1)];
+        let standings = standings(
+            &rules(&[TieBreaker::AveragePlacement, TieBreaker::TotalPoints]),
+            &players(&["a", "b", "c"]),
+            &results,
+        );
+
+        assert_eq!(positions(&standings), [("b", 1), ("a", 2), ("c", 3)]);
+        assert_eq!(standings[2].games, 0);
+    }
+
+    #[test]
+    fn standings_ignore_unregistered_players() {
+        let results = [result("a", 30000, 2), result("substitute", 40000, 1)];
+        let standings = standings(&rules(&[]), &players(&["a"]), &results);
+
+        assert_eq!(positions(&standings), [("a", 1)]);
+    }
+}
@@ -1,10 +1,14 @@
 use axum::{async_trait, extract::{FromRequest, RequestParts}, response::{Response, IntoResponse}, Json, Router};
-use axum::routing::get;
+use axum::routing::{get, post};
+use serde::Deserialize;
+use sqlx::Connection;
+use validator::Validate;
 use hyper::StatusCode;
 use serde_json::json;
 
 use crate::{firebase::FirebaseClaims, db::DatabaseConnection, firebase, users};
 use crate::app::AppError;
+use crate::validate::ValidatedJson;
 
 pub struct CurrentUser {
     pub user_uid: String,
@@ -41,6 +45,35 @@ impl<B> FromRequest<B> for CurrentUser
     }
 }
 
+/// User allowed to perform administrative operations,
+/// i.e. merging players or unlinking accounts
+pub struct CurrentAdmin {
+    pub user_uid: String,
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for CurrentAdmin
+    where
+        B: Send,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let claims = req.extract::<FirebaseClaims>().await.expect("firebase claims are gone");
+        let conn = req.extract::<DatabaseConnection>().await.expect("db connection is gone");
+        let DatabaseConnection(mut conn) = conn;
+
+        sqlx::query_scalar!("SELECT 1 FROM user_admin WHERE user_uid = ?", claims.sub)
+            .fetch_optional(&mut conn)
+            .await?
+            .ok_or(AppError::Forbidden)?;
+
+        Ok(CurrentAdmin {
+            user_uid: claims.sub,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum CurrentUserError {
     NotAssigned,
@@ -67,6 +100,10 @@ pub fn router() -> Router {
             "/users/@me",
             get(users_me),
         )
+        .route(
+            "/users/@me/claim",
+            post(users_me_claim),
+        )
 }
 
 pub async fn users_me(
@@ -82,4 +119,106 @@ pub async fn users_me(
         ],
         "count": 1,
     })))
-}
\ No newline at end of file
+}
+
+/// Failed attempts allowed per user within `CLAIM_ATTEMPTS_WINDOW` seconds
+const CLAIM_ATTEMPTS_LIMIT: i64 = 5;
+const CLAIM_ATTEMPTS_WINDOW: i64 = 15 * 60;
+
+#[derive(Deserialize, Validate)]
+pub struct UsersMeClaim {
+    #[validate(length(min = 1, max = 32))]
+    code: String,
+}
+
+/// Links signed-in user to the player using one-time claim code,
+/// user must not be assigned to any player yet
+pub async fn users_me_claim(
+    claims: firebase::FirebaseClaims,
+    ValidatedJson(input): ValidatedJson<UsersMeClaim>,
+    DatabaseConnection(conn): DatabaseConnection,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = conn;
+    let user_uid = claims.sub.as_str();
+    let code = input.code.trim().to_uppercase();
+
+    let failed_attempts = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count: i64" FROM user_claim_attempts
+        WHERE user_uid = ? AND is_success = 0 AND created_at > strftime('%s', 'now') - ?"#,
+        user_uid,
+        CLAIM_ATTEMPTS_WINDOW,
+    )
+    .fetch_one(&mut conn)
+    .await?;
+
+    if failed_attempts >= CLAIM_ATTEMPTS_LIMIT {
+        return Err(AppError::TooManyRequests);
+    }
+
+    let mut tx = conn.begin().await?;
+
+    let is_assigned = sqlx::query_scalar!("SELECT 1 FROM user_player WHERE user_uid = ?", user_uid)
+        .fetch_optional(&mut tx)
+        .await?
+        .is_some();
+
+    if is_assigned {
+        return Err(AppError::UserAlreadyAssigned);
+    }
+
+    // marking code as redeemed first makes sure it's used only once
+    let player_uuid = sqlx::query_scalar!(
+        "UPDATE player_claim_codes SET redeemed_at = strftime('%s', 'now'), redeemed_by = ?1
+        WHERE code = ?2 AND redeemed_at IS NULL AND expires_at > strftime('%s', 'now')
+        RETURNING player_uuid",
+        user_uid,
+        code,
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let player_uuid = match player_uuid {
+        Some(player_uuid) => player_uuid,
+        None => {
+            tx.rollback().await?;
+
+            sqlx::query!(
+                "INSERT INTO user_claim_attempts (user_uid, is_success, created_at)
+                VALUES (?, 0, strftime('%s', 'now'))",
+                user_uid,
+            )
+            .execute(&mut conn)
+            .await?;
+
+            return Err(AppError::ClaimCodeInvalid);
+        }
+    };
+
+    sqlx::query!(
+        "INSERT INTO user_player (user_uid, player_uuid) VALUES (?, ?)",
+        user_uid,
+        player_uuid,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO user_claim_attempts (user_uid, is_success, created_at)
+        VALUES (?, 1, strftime('%s', 'now'))",
+        user_uid,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(json!({
+        "items": vec![
+            json!({
+                "user_uid": user_uid,
+                "player_uuid": player_uuid,
+            })
+        ],
+        "count": 1,
+    })))
+}
@@ -5,7 +5,7 @@ use axum::{
     BoxError, Json,
 };
 use futures_util::TryFutureExt;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
 use validator::Validate;
 
 use crate::app::AppError;
@@ -93,3 +93,13 @@ where
         Ok(ValidatedQuery(value))
     }
 }
+
+/// Deserializes present field as `Some`, so together with `#[serde(default)]` missing field
+/// (`None`) is told apart from explicit null (`Some(None)`) which clears the value
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}